@@ -0,0 +1,141 @@
+/// Verbs that open an action item when a bullet starts with them.
+const ACTION_VERBS: &[&str] = &[
+    "add",
+    "adopt",
+    "agree",
+    "assign",
+    "audit",
+    "automate",
+    "book",
+    "build",
+    "call",
+    "cancel",
+    "check",
+    "choose",
+    "collect",
+    "commit",
+    "contact",
+    "create",
+    "cut",
+    "decide",
+    "define",
+    "deploy",
+    "document",
+    "draft",
+    "email",
+    "establish",
+    "evaluate",
+    "expand",
+    "finalize",
+    "fix",
+    "follow",
+    "hire",
+    "identify",
+    "implement",
+    "interview",
+    "introduce",
+    "investigate",
+    "launch",
+    "meet",
+    "migrate",
+    "monitor",
+    "move",
+    "negotiate",
+    "onboard",
+    "organize",
+    "pilot",
+    "plan",
+    "prepare",
+    "prioritize",
+    "publish",
+    "raise",
+    "reduce",
+    "remove",
+    "replace",
+    "research",
+    "review",
+    "run",
+    "schedule",
+    "send",
+    "set",
+    "ship",
+    "start",
+    "stop",
+    "survey",
+    "test",
+    "track",
+    "train",
+    "update",
+    "validate",
+    "write",
+];
+
+/// Pulls the action items out of an answer: every numbered step, and
+/// every bullet that opens with an action verb. Markers are stripped.
+pub fn extract(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (item, numbered) = match strip_number(line) {
+                Some(item) => (item, true),
+                None => (strip_bullet(line)?, false),
+            };
+            let item = item.trim_start_matches("[ ]").trim();
+            if item.is_empty() {
+                return None;
+            }
+            (numbered || opens_with_action(item)).then(|| item.to_string())
+        })
+        .collect()
+}
+
+fn strip_number(line: &str) -> Option<&str> {
+    let digits = line.find(|c: char| !c.is_ascii_digit())?;
+    if digits == 0 {
+        return None;
+    }
+    line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))
+}
+
+fn strip_bullet(line: &str) -> Option<&str> {
+    ["- ", "* ", "• "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+}
+
+fn opens_with_action(item: &str) -> bool {
+    let first = item
+        .trim_start_matches(|c: char| !c.is_alphanumeric())
+        .split(|c: char| !c.is_alphabetic())
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+    ACTION_VERBS.contains(&first.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extracts_steps_and_imperative_bullets() {
+        let answer = "Growth looks strong.\n\
+                      1. Raise prices on annual plans\n\
+                      2) The churn review\n\
+                      - Hire two account managers\n\
+                      - Revenue grew 10% last quarter\n\
+                      * [ ] **Schedule** a pricing review\n\
+                      2024 was a good year.";
+        assert_eq!(
+            extract(answer),
+            vec![
+                "Raise prices on annual plans",
+                "The churn review",
+                "Hire two account managers",
+                "**Schedule** a pricing review",
+            ]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// A request slower than this multiple of the baseline latency counts as
+/// degraded.
+const LATENCY_TOLERANCE: f64 = 2.0;
+/// Factor the limit shrinks by on a degraded request.
+const BACKOFF: f64 = 0.9;
+/// Weight of one healthy request in the baseline's moving average.
+const BASELINE_WEIGHT: f64 = 0.1;
+/// Weight of one degraded request, so a permanently slower backend isn't
+/// treated as degraded forever.
+const BASELINE_DRIFT: f64 = 0.01;
+
+#[derive(Debug)]
+struct Gauge {
+    limit: f64,
+    /// Latency (ms) the backend manages when it isn't overloaded: an
+    /// exponentially weighted average of completed requests.
+    baseline_ms: Option<f64>,
+}
+
+/// Concurrency limiter that finds its own limit with AIMD: the limit grows
+/// by one per window of healthy requests and shrinks by `BACKOFF` whenever a
+/// request takes noticeably longer than the baseline.
+pub struct AdaptiveLimit {
+    min: usize,
+    max: usize,
+    gauge: Mutex<Gauge>,
+    in_flight: AtomicUsize,
+    released: Notify,
+}
+
+pub struct AdaptivePermit {
+    limiter: Arc<AdaptiveLimit>,
+    started: Instant,
+}
+
+impl AdaptiveLimit {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        Self {
+            min,
+            max: max.max(min),
+            gauge: Mutex::new(Gauge {
+                limit: min as f64,
+                baseline_ms: None,
+            }),
+            in_flight: AtomicUsize::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Current concurrency limit.
+    pub fn limit(&self) -> usize {
+        let gauge = self.gauge.lock().unwrap_or_else(|e| e.into_inner());
+        gauge.limit as usize
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Waits until a slot is available under the current limit.
+    pub async fn acquire(self: &Arc<Self>) -> AdaptivePermit {
+        loop {
+            let notified = self.released.notified();
+            let current = self.in_flight.load(Ordering::Acquire);
+            if current < self.limit() {
+                if self
+                    .in_flight
+                    .compare_exchange(current, current + 1, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    return AdaptivePermit {
+                        limiter: self.clone(),
+                        started: Instant::now(),
+                    };
+                }
+                continue;
+            }
+            notified.await;
+        }
+    }
+
+    /// Adjusts the limit for one completed request.
+    pub fn record(&self, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        let mut gauge = self.gauge.lock().unwrap_or_else(|e| e.into_inner());
+        let baseline = *gauge.baseline_ms.get_or_insert(ms);
+
+        let weight = if ms > baseline * LATENCY_TOLERANCE {
+            gauge.limit = (gauge.limit * BACKOFF).max(self.min as f64);
+            BASELINE_DRIFT
+        } else {
+            gauge.limit = (gauge.limit + 1.0 / gauge.limit).min(self.max as f64);
+            BASELINE_WEIGHT
+        };
+        gauge.baseline_ms = Some(baseline + (ms - baseline) * weight);
+    }
+}
+
+impl AdaptivePermit {
+    /// Records the time since the slot was taken as one completed request.
+    /// Only permits marked completed feed the limit.
+    pub fn completed(&self) {
+        self.limiter.record(self.started.elapsed());
+    }
+}
+
+impl Drop for AdaptivePermit {
+    fn drop(&mut self) {
+        self.limiter.in_flight.fetch_sub(1, Ordering::AcqRel);
+        // Growing the limit may free more than one slot
+        self.limiter.released.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grows_while_healthy_and_backs_off() {
+        let limiter = AdaptiveLimit::new(2, 4);
+        assert_eq!(limiter.limit(), 2);
+
+        for _ in 0..20 {
+            limiter.record(Duration::from_millis(100));
+        }
+        assert_eq!(limiter.limit(), 4);
+
+        limiter.record(Duration::from_millis(1000));
+        assert_eq!(limiter.limit(), 3);
+
+        // Never below the floor
+        for _ in 0..20 {
+            limiter.record(Duration::from_millis(1000));
+        }
+        assert_eq!(limiter.limit(), 2);
+    }
+
+    #[test]
+    fn test_one_fast_request_does_not_reset_the_baseline() {
+        let limiter = AdaptiveLimit::new(2, 8);
+        for _ in 0..20 {
+            limiter.record(Duration::from_millis(100));
+        }
+        let limit = limiter.limit();
+
+        // A cache-hit-fast outlier barely moves the average, so ordinary
+        // requests afterwards still count as healthy
+        limiter.record(Duration::from_millis(1));
+        limiter.record(Duration::from_millis(150));
+        assert!(limiter.limit() >= limit);
+    }
+
+    #[tokio::test]
+    async fn test_only_completed_permits_are_recorded() {
+        let limiter = Arc::new(AdaptiveLimit::new(2, 4));
+        for _ in 0..20 {
+            drop(limiter.acquire().await);
+        }
+        assert_eq!(limiter.limit(), 2);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Conversations idle this long are forgotten, budget and all.
+const IDLE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Tokens spent so far per conversation, for `conversation_token_budget`.
+#[derive(Default)]
+pub struct TokenLedger {
+    spent: Mutex<HashMap<String, (Instant, u64)>>,
+}
+
+impl TokenLedger {
+    pub fn spent(&self, conversation: &str) -> u64 {
+        let spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        spent.get(conversation).map_or(0, |(_, tokens)| *tokens)
+    }
+
+    /// Adds `tokens` to the conversation's total and returns the new total.
+    pub fn charge(&self, conversation: &str, tokens: u64) -> u64 {
+        let now = Instant::now();
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        spent.retain(|_, (last, _)| now.duration_since(*last) < IDLE_TTL);
+
+        let entry = spent.entry(conversation.to_string()).or_insert((now, 0));
+        *entry = (now, entry.1 + tokens);
+        entry.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charges_accumulate_per_conversation() {
+        let ledger = TokenLedger::default();
+        assert_eq!(ledger.charge("a", 100), 100);
+        assert_eq!(ledger.charge("a", 50), 150);
+        assert_eq!(ledger.charge("b", 10), 10);
+        assert_eq!(ledger.spent("a"), 150);
+        assert_eq!(ledger.spent("unknown"), 0);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::knowledge::ScoredChunk;
+use serde::{Deserialize, Serialize};
+
+/// Instruction added to the system prompt when citations are on.
+pub const INSTRUCTION: &str =
+    "Cite the knowledge you rely on with its [n] marker, placed right after the claim.";
+
+/// How `[n]` citation markers are rendered in the answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationStyle {
+    /// Markers stay as `[n]`, with a list of sources appended.
+    Footnotes,
+    /// Markers become Markdown links to the chunk: `[source](chunk-id)`.
+    Inline,
+}
+
+/// One injected knowledge chunk and the marker that cites it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextRef {
+    pub marker: usize,
+    pub chunk_id: String,
+    pub source: String,
+    /// The answer cites this chunk.
+    pub cited: bool,
+}
+
+/// Knowledge text as injected into the prompt, prefixed with its marker.
+pub fn label(marker: usize, text: &str) -> String {
+    format!("[{}] {}", marker, text)
+}
+
+/// Renders the `[n]` markers in `answer` in `style`, returning the
+/// rendered answer, the marker-to-chunk mapping and the markers that
+/// didn't match any injected chunk (which are dropped).
+pub fn render(
+    answer: &str,
+    grounding: &[ScoredChunk],
+    style: CitationStyle,
+) -> (String, Vec<ContextRef>, Vec<String>) {
+    let mut context: Vec<ContextRef> = grounding
+        .iter()
+        .enumerate()
+        .map(|(i, c)| ContextRef {
+            marker: i + 1,
+            chunk_id: c.chunk.id.clone(),
+            source: c.chunk.source.clone(),
+            cited: false,
+        })
+        .collect();
+    let mut invalid = Vec::new();
+    let mut rendered = String::with_capacity(answer.len());
+
+    let mut rest = answer;
+    while let Some(open) = rest.find('[') {
+        rendered.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let marker = after
+            .find(']')
+            .and_then(|close| after[..close].parse::<usize>().ok().map(|n| (n, close)));
+        let Some((n, close)) = marker else {
+            rendered.push('[');
+            rest = after;
+            continue;
+        };
+        rest = &after[close + 1..];
+
+        match context.get_mut(n.wrapping_sub(1)) {
+            Some(cited) => {
+                cited.cited = true;
+                match style {
+                    CitationStyle::Footnotes => rendered.push_str(&format!("[{}]", n)),
+                    CitationStyle::Inline => {
+                        rendered.push_str(&format!("[{}]({})", cited.source, cited.chunk_id))
+                    }
+                }
+            }
+            None => {
+                invalid.push(format!("[{}]", n));
+                // Don't leave a doubled space where the marker was
+                if rendered.ends_with(' ') && (rest.is_empty() || rest.starts_with([' ', '.', ',']))
+                {
+                    rendered.pop();
+                }
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    if style == CitationStyle::Footnotes && context.iter().any(|c| c.cited) {
+        rendered.push_str("\n\nSources:");
+        for c in context.iter().filter(|c| c.cited) {
+            rendered.push_str(&format!("\n[{}] {}", c.marker, c.chunk_id));
+        }
+    }
+    (rendered, context, invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::knowledge::KnowledgeIndex;
+    use crate::Intent;
+
+    #[test]
+    fn test_render_validates_markers() {
+        let mut index = KnowledgeIndex::default();
+        index.add_document("pricing.md", "Annual plans get a discount.");
+        let grounding = index.retrieve("annual plans", &Intent::Strategy, None, 1);
+
+        let (text, context, invalid) = render(
+            "Offer annual plans [1] and a free tier [7].",
+            &grounding,
+            CitationStyle::Footnotes,
+        );
+        assert_eq!(
+            text,
+            "Offer annual plans [1] and a free tier.\n\nSources:\n[1] pricing.md#0"
+        );
+        assert!(context[0].cited);
+        assert_eq!(invalid, vec!["[7]"]);
+
+        let (text, _, _) = render("Offer annual plans [1].", &grounding, CitationStyle::Inline);
+        assert_eq!(text, "Offer annual plans [pricing.md](pricing.md#0).");
+    }
+}
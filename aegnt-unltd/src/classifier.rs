@@ -0,0 +1,12 @@
+use crate::Intent;
+use async_trait::async_trait;
+
+/// A classifier that replaces the built-in rules, e.g. a small local model.
+/// Subject to `classification_timeout_ms`; past it the built-in rules
+/// decide. Implementations doing blocking work should move it off the
+/// runtime (`spawn_blocking`) so the timeout can fire.
+#[async_trait]
+pub trait Classifier: Send + Sync {
+    /// Intent of `input`, the reason for it, and a confidence (0-1).
+    async fn classify(&self, input: &str) -> (Intent, String, f32);
+}
@@ -0,0 +1,217 @@
+use crate::{signing, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Timeouts for one backend, so a nearby local backend can fail fast while
+/// a distant cloud one gets room to answer. Unset values leave the HTTP
+/// client's default (no timeout).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BackendTimeouts {
+    /// Longest to wait for the connection to open.
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// Longest to wait between reads of the response.
+    #[serde(default)]
+    pub read_timeout_ms: Option<u64>,
+}
+
+/// Connection pool for one backend, shared by every request to it so
+/// connections are reused instead of opened per call. Unset values leave
+/// the HTTP client's default.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PoolSettings {
+    /// Idle connections kept open per host.
+    #[serde(default)]
+    pub max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept before it's closed.
+    #[serde(default)]
+    pub idle_timeout_ms: Option<u64>,
+}
+
+/// Shared HTTP clients, one per backend URL and timeout combination, so
+/// every route to a backend draws on the same connection pool.
+#[derive(Debug, Default)]
+pub struct HttpPools {
+    /// Pool settings by backend base URL.
+    pools: HashMap<String, PoolSettings>,
+    clients: HashMap<(String, BackendTimeouts), reqwest::Client>,
+}
+
+impl HttpPools {
+    pub fn new(pools: &HashMap<String, PoolSettings>) -> Self {
+        Self {
+            pools: pools
+                .iter()
+                .map(|(url, pool)| (url.trim_end_matches('/').to_string(), *pool))
+                .collect(),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// The client for `base_url` with `timeouts`, built on first use.
+    pub fn client(&mut self, base_url: &str, timeouts: BackendTimeouts) -> reqwest::Client {
+        let base_url = base_url.trim_end_matches('/');
+        let pool = self.pools.get(base_url).copied().unwrap_or_default();
+        self.clients
+            .entry((base_url.to_string(), timeouts))
+            .or_insert_with(|| http_client(pool, timeouts))
+            .clone()
+    }
+}
+
+fn http_client(pool: PoolSettings, timeouts: BackendTimeouts) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+    if let Some(max) = pool.max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(ms) = pool.idle_timeout_ms {
+        builder = builder.pool_idle_timeout(Duration::from_millis(ms));
+    }
+    if let Some(ms) = timeouts.connect_timeout_ms {
+        builder = builder.connect_timeout(Duration::from_millis(ms));
+    }
+    if let Some(ms) = timeouts.read_timeout_ms {
+        builder = builder.read_timeout(Duration::from_millis(ms));
+    }
+    builder.build().unwrap_or_default()
+}
+
+/// HTTP client for a remote brain's `/process` endpoint.
+#[derive(Debug, Clone)]
+pub struct BrainClient {
+    base_url: String,
+    http: reqwest::Client,
+    verification_key: Option<String>,
+}
+
+impl BrainClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            verification_key: None,
+        }
+    }
+
+    /// Applies `timeouts` to every request to this brain.
+    pub fn with_timeouts(mut self, timeouts: BackendTimeouts) -> Self {
+        self.http = http_client(PoolSettings::default(), timeouts);
+        self
+    }
+
+    /// Sends requests through `http`, e.g. a client shared from `HttpPools`.
+    pub fn with_http(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Requires every response to carry a valid signature for `key`.
+    pub fn with_verification_key(mut self, key: impl Into<String>) -> Self {
+        self.verification_key = Some(key.into());
+        self
+    }
+
+    /// Checks a response's signature against `key`. Unsigned responses fail.
+    pub fn verify(response: &Response, key: &str) -> bool {
+        response.signature.as_deref().is_some_and(|signature| {
+            signing::verify(
+                key,
+                &response.request_id,
+                &response.input_hash,
+                &response.content,
+                signature,
+            )
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub async fn process(&self, input: &str, seed: Option<u64>) -> Result<Response, String> {
+        let res = self
+            .http
+            .post(format!("{}/process", self.base_url))
+            .json(&json!({ "input": input, "seed": seed }))
+            .send()
+            .await
+            .map_err(|e| format!("request to {} failed: {}", self.base_url, e))?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(format!("{} returned {}: {}", self.base_url, status, body));
+        }
+
+        let response = res
+            .json::<Response>()
+            .await
+            .map_err(|e| format!("invalid response from {}: {}", self.base_url, e))?;
+
+        if let Some(key) = &self.verification_key {
+            if !Self::verify(&response, key) {
+                return Err(format!(
+                    "response from {} failed signature verification",
+                    self.base_url
+                ));
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_timeout_fails_fast() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let client = BrainClient::new(format!("http://{}", addr)).with_timeouts(BackendTimeouts {
+            connect_timeout_ms: Some(100),
+            read_timeout_ms: Some(50),
+        });
+        let started = std::time::Instant::now();
+        let err = client.process("hello", None).await.unwrap_err();
+        assert!(err.starts_with("request to"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_pools_are_shared_per_backend() {
+        let mut pools = HttpPools::new(&HashMap::from([(
+            "http://ollama:11434/".to_string(),
+            PoolSettings {
+                max_idle_per_host: Some(32),
+                idle_timeout_ms: Some(90_000),
+            },
+        )]));
+        let fast = BackendTimeouts {
+            connect_timeout_ms: Some(100),
+            read_timeout_ms: None,
+        };
+        pools.client("http://ollama:11434", BackendTimeouts::default());
+        pools.client("http://ollama:11434/", BackendTimeouts::default());
+        assert_eq!(pools.clients.len(), 1);
+        assert_eq!(
+            pools.pools["http://ollama:11434"].max_idle_per_host,
+            Some(32)
+        );
+
+        pools.client("http://ollama:11434", fast);
+        pools.client("http://other:8080", BackendTimeouts::default());
+        assert_eq!(pools.clients.len(), 3);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::Intent;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Histogram buckets over 0-1, each `1 / BUCKETS` wide.
+const BUCKETS: usize = 10;
+
+/// Distribution of classifier confidence for one intent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Histogram {
+    /// Requests per confidence bucket, lowest first.
+    pub buckets: [u64; BUCKETS],
+    pub count: u64,
+    pub mean: f32,
+}
+
+impl Histogram {
+    fn record(&mut self, confidence: f32) {
+        let bucket = ((confidence.clamp(0.0, 1.0) * BUCKETS as f32) as usize).min(BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.mean += (confidence - self.mean) / self.count as f32;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (bucket, n) in self.buckets.iter_mut().zip(other.buckets) {
+            *bucket += n;
+        }
+        let count = self.count + other.count;
+        if count > 0 {
+            self.mean =
+                (self.mean * self.count as f32 + other.mean * other.count as f32) / count as f32;
+        }
+        self.count = count;
+    }
+}
+
+/// Confidence histograms for one period.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfidenceWindow {
+    /// Unix time (seconds) the period started.
+    pub started_at: u64,
+    pub intents: HashMap<Intent, Histogram>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfidenceReport {
+    pub period_secs: u64,
+    /// Totals across every retained period.
+    pub overall: HashMap<Intent, Histogram>,
+    /// Retained periods, oldest first; a falling mean across them is drift.
+    pub windows: Vec<ConfidenceWindow>,
+}
+
+/// Classifier confidence per request, bucketed into fixed periods so the
+/// distribution can be compared over time.
+pub struct ConfidenceLog {
+    period: Duration,
+    retained: usize,
+    windows: Mutex<VecDeque<ConfidenceWindow>>,
+}
+
+impl ConfidenceLog {
+    pub fn new(period: Duration, retained: usize) -> Self {
+        Self {
+            period: period.max(Duration::from_secs(1)),
+            retained: retained.max(1),
+            windows: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn record(&self, intent: &Intent, confidence: f32) {
+        self.record_at(now(), intent, confidence);
+    }
+
+    fn record_at(&self, at: u64, intent: &Intent, confidence: f32) {
+        let started_at = at - at % self.period.as_secs();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.back().is_none_or(|w| w.started_at < started_at) {
+            if windows.len() == self.retained {
+                windows.pop_front();
+            }
+            windows.push_back(ConfidenceWindow {
+                started_at,
+                intents: HashMap::new(),
+            });
+        }
+        if let Some(window) = windows.back_mut() {
+            window
+                .intents
+                .entry(intent.clone())
+                .or_default()
+                .record(confidence);
+        }
+    }
+
+    pub fn report(&self) -> ConfidenceReport {
+        let windows: Vec<ConfidenceWindow> = self
+            .windows
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect();
+
+        let mut overall: HashMap<Intent, Histogram> = HashMap::new();
+        for window in &windows {
+            for (intent, histogram) in &window.intents {
+                overall.entry(intent.clone()).or_default().merge(histogram);
+            }
+        }
+        ConfidenceReport {
+            period_secs: self.period.as_secs(),
+            overall,
+            windows,
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histograms_per_period() {
+        let log = ConfidenceLog::new(Duration::from_secs(60), 2);
+        log.record_at(0, &Intent::Strategy, 0.95);
+        log.record_at(30, &Intent::Strategy, 0.85);
+        log.record_at(60, &Intent::Strategy, 0.45);
+        log.record_at(61, &Intent::QuickAction, 1.0);
+
+        let report = log.report();
+        assert_eq!(report.windows.len(), 2);
+        let first = &report.windows[0].intents[&Intent::Strategy];
+        assert_eq!((first.buckets[8], first.buckets[9]), (1, 1));
+        assert!((first.mean - 0.9).abs() < 1e-6);
+        assert_eq!(
+            report.windows[1].intents[&Intent::QuickAction].buckets[9],
+            1
+        );
+
+        let overall = &report.overall[&Intent::Strategy];
+        assert_eq!(overall.count, 3);
+        assert!((overall.mean - 0.75).abs() < 1e-6);
+
+        // Only the newest `retained` periods are kept
+        log.record_at(120, &Intent::Strategy, 0.5);
+        assert_eq!(log.report().windows[0].started_at, 60);
+    }
+}
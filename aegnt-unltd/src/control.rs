@@ -0,0 +1,66 @@
+/// An operational command sent inline as a directive (e.g. `::reindex
+/// knowledge`) and handled by the brain instead of a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    ReindexKnowledge,
+    ReloadTemplates,
+    ReloadOverrides,
+    Status,
+}
+
+const COMMANDS: &[(&str, ControlCommand)] = &[
+    ("reindex knowledge", ControlCommand::ReindexKnowledge),
+    ("reload templates", ControlCommand::ReloadTemplates),
+    ("reload overrides", ControlCommand::ReloadOverrides),
+    ("status", ControlCommand::Status),
+];
+
+/// Parses `input` as a control directive if it starts with `prefix`.
+/// Returns `None` for ordinary directives and an error for unrecognized
+/// commands.
+pub fn parse(input: &str, prefix: &str) -> Option<Result<ControlCommand, String>> {
+    let command = input.trim_start().strip_prefix(prefix)?;
+    let command = command
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    Some(
+        COMMANDS
+            .iter()
+            .find(|(name, _)| *name == command)
+            .map(|(_, cmd)| *cmd)
+            .ok_or_else(|| {
+                let known: Vec<String> = COMMANDS
+                    .iter()
+                    .map(|(name, _)| format!("{}{}", prefix, name))
+                    .collect();
+                format!(
+                    "unknown control directive '{}{}'; expected one of: {}",
+                    prefix,
+                    command,
+                    known.join(", ")
+                )
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_control_directives() {
+        assert_eq!(parse("What time is it?", "::"), None);
+        assert_eq!(
+            parse("  ::Reindex   Knowledge", "::"),
+            Some(Ok(ControlCommand::ReindexKnowledge))
+        );
+
+        let err = parse("::switch persona to legal", "::")
+            .unwrap()
+            .unwrap_err();
+        assert!(err.starts_with("unknown control directive '::switch persona to legal'"));
+    }
+}
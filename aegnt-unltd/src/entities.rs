@@ -0,0 +1,173 @@
+use crate::Intent;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Kinds of entity the extractor recognises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    /// `$40k`, `€1,200`, `£3.50`.
+    Money,
+    /// `2025-03-01`, `3/1/25`, `March`, `Q3`, `2025`.
+    Date,
+    /// `15%`.
+    Percentage,
+    Email,
+    Url,
+}
+
+/// Month names and abbreviations; "may" is left out as too often a verb.
+const MONTHS: &[&str] = &[
+    "january",
+    "february",
+    "march",
+    "april",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+    "jan",
+    "feb",
+    "mar",
+    "apr",
+    "jun",
+    "jul",
+    "aug",
+    "sep",
+    "sept",
+    "oct",
+    "nov",
+    "dec",
+];
+
+/// Distinct kinds of entity in `input`.
+pub fn kinds(input: &str) -> BTreeSet<EntityKind> {
+    input.split_whitespace().filter_map(kind).collect()
+}
+
+fn kind(word: &str) -> Option<EntityKind> {
+    let lower = word.to_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        return Some(EntityKind::Url);
+    }
+    let token = lower
+        .trim_matches(|c: char| matches!(c, ',' | '.' | ';' | ':' | '!' | '?' | '(' | ')' | '"'));
+    if token.contains('@')
+        && token
+            .rsplit('@')
+            .next()
+            .is_some_and(|domain| domain.contains('.'))
+    {
+        return Some(EntityKind::Email);
+    }
+    if token.len() > 1 && token.ends_with('%') && is_number(&token[..token.len() - 1]) {
+        return Some(EntityKind::Percentage);
+    }
+    if let Some(amount) = token.strip_prefix(['$', '€', '£']) {
+        let amount = amount.trim_end_matches(['k', 'm', 'b']);
+        if is_number(amount) {
+            return Some(EntityKind::Money);
+        }
+    }
+    is_date(token).then_some(EntityKind::Date)
+}
+
+fn is_number(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_digit())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+}
+
+fn is_date(token: &str) -> bool {
+    let numeric = |sep: char| {
+        let parts: Vec<&str> = token.split(sep).collect();
+        parts.len() == 3
+            && parts
+                .iter()
+                .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+    };
+    let year = token.len() == 4
+        && (token.starts_with("19") || token.starts_with("20"))
+        && is_number(token);
+    let quarter = matches!(token, "q1" | "q2" | "q3" | "q4");
+    year || quarter || MONTHS.contains(&token) || numeric('-') || numeric('/')
+}
+
+/// How the kinds of entity in an input bias classification. Positive
+/// weights pull toward Strategy, negative toward QuickAction; the bias
+/// overrides the rules before it when it beats their confidence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityRules {
+    /// Weight of each kind present.
+    pub weights: HashMap<EntityKind, f32>,
+    /// Added once at least `min_kinds` distinct kinds are present.
+    pub combined: f32,
+    pub min_kinds: usize,
+}
+
+impl Default for EntityRules {
+    fn default() -> Self {
+        Self {
+            weights: HashMap::from([(EntityKind::Money, 0.3), (EntityKind::Date, 0.3)]),
+            combined: 0.4,
+            min_kinds: 2,
+        }
+    }
+}
+
+impl EntityRules {
+    /// Combined bias of the entities in `input`, with the kinds found.
+    pub fn score(&self, input: &str) -> (f32, BTreeSet<EntityKind>) {
+        let kinds = kinds(input);
+        let mut score: f32 = kinds.iter().filter_map(|kind| self.weights.get(kind)).sum();
+        if kinds.len() >= self.min_kinds.max(1) {
+            score += self.combined;
+        }
+        (score, kinds)
+    }
+
+    /// The intent a bias of `score` pulls toward.
+    pub fn intent(score: f32) -> Intent {
+        if score > 0.0 {
+            Intent::Strategy
+        } else {
+            Intent::QuickAction
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extracts_entity_kinds() {
+        let found =
+            kinds("Spend $40k by Q3 (about 15% of budget); details at https://x.io or ops@x.io.");
+        assert_eq!(
+            found.into_iter().collect::<Vec<_>>(),
+            vec![
+                EntityKind::Money,
+                EntityKind::Date,
+                EntityKind::Percentage,
+                EntityKind::Email,
+                EntityKind::Url,
+            ]
+        );
+        assert!(kinds("It may be 40 minutes away").is_empty());
+    }
+
+    #[test]
+    fn test_money_and_date_bias_toward_strategy() {
+        let rules = EntityRules::default();
+        let (score, _) = rules.score("Put $2,000 aside before 2025-06-01");
+        assert!((score - 1.0).abs() < 1e-6);
+        assert_eq!(EntityRules::intent(score), Intent::Strategy);
+        assert_eq!(rules.score("Send it to ops@x.io").0, 0.0);
+    }
+}
@@ -0,0 +1,77 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Pipeline phase a request failed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// The request itself: an unparseable directive or unknown persona.
+    Request,
+    Classify,
+    Grounding,
+    /// Assembling the prompt within `max_prompt_tokens`.
+    Prompt,
+    Generation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    InvalidRequest,
+    PromptTooLarge,
+    BackendUnavailable,
+    BackendTimeout,
+    Internal,
+}
+
+impl ErrorCategory {
+    /// Whether the same request might succeed if sent again.
+    pub fn retryable(self) -> bool {
+        matches!(self, Self::BackendUnavailable | Self::BackendTimeout)
+    }
+}
+
+/// Why a directive failed, with enough context for a client to decide
+/// whether to retry and what to report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessError {
+    pub phase: Phase,
+    pub category: ErrorCategory,
+    /// Backend that failed, when one did.
+    pub backend: Option<String>,
+    pub message: String,
+    /// Id the response would have had.
+    pub request_id: Option<String>,
+    pub retryable: bool,
+}
+
+impl ProcessError {
+    pub fn new(phase: Phase, category: ErrorCategory, message: impl Into<String>) -> Self {
+        Self {
+            phase,
+            category,
+            backend: None,
+            message: message.into(),
+            request_id: None,
+            retryable: category.retryable(),
+        }
+    }
+
+    pub fn with_backend(mut self, backend: impl Into<String>) -> Self {
+        self.backend = Some(backend.into());
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ProcessError {}
@@ -0,0 +1,94 @@
+use crate::entities::{self, EntityKind};
+use crate::{Intent, RoutingDecision, StructuralWeights, QUICK_KEYWORDS, STRATEGY_KEYWORDS};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// What the classifier saw in one input, with the intent it settled on: a
+/// labelled example for training a learned classifier.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureRecord {
+    pub request_id: String,
+    /// Strategy keywords the input contains.
+    pub strategy_keywords: Vec<String>,
+    /// Quick-action keywords the input contains.
+    pub quick_keywords: Vec<String>,
+    /// Structural cues present, whether or not they're weighted.
+    pub structural_cues: Vec<String>,
+    pub entity_kinds: Vec<EntityKind>,
+    pub chars: usize,
+    pub words: usize,
+    /// `crate::complexity` of the input.
+    pub complexity: f32,
+    pub classified_intent: Intent,
+    pub final_intent: Intent,
+    pub confidence: f32,
+}
+
+impl FeatureRecord {
+    pub fn extract(request_id: &str, input: &str, routing: &RoutingDecision) -> Self {
+        let lower = input.to_lowercase();
+        let matching = |keywords: &[&str]| {
+            keywords
+                .iter()
+                .filter(|k| lower.contains(*k))
+                .map(|k| k.to_string())
+                .collect()
+        };
+        let (_, cues) = crate::structural_signal(input, &StructuralWeights::default());
+        Self {
+            request_id: request_id.to_string(),
+            strategy_keywords: matching(STRATEGY_KEYWORDS),
+            quick_keywords: matching(QUICK_KEYWORDS),
+            structural_cues: cues.into_iter().map(str::to_string).collect(),
+            entity_kinds: entities::kinds(input).into_iter().collect(),
+            chars: input.chars().count(),
+            words: input.split_whitespace().count(),
+            complexity: crate::complexity(input),
+            classified_intent: routing.classified_intent.clone(),
+            final_intent: routing.final_intent.clone(),
+            confidence: routing.confidence,
+        }
+    }
+}
+
+/// One line of the feature log. Corrections arrive later than the
+/// features they relabel and are joined to them by `request_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FeatureLogEntry {
+    Features(FeatureRecord),
+    Correction { request_id: String, intent: Intent },
+}
+
+/// Append-only JSONL file of `FeatureLogEntry`s.
+pub struct FeatureLog {
+    path: PathBuf,
+    // Keeps concurrent appends from interleaving
+    write: Mutex<()>,
+}
+
+impl FeatureLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            write: Mutex::new(()),
+        }
+    }
+
+    pub async fn append(&self, entry: &FeatureLogEntry) -> Result<(), String> {
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let _guard = self.write.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| format!("feature log {:?}: {}", self.path, e))?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("feature log {:?}: {}", self.path, e))
+    }
+}
@@ -0,0 +1,73 @@
+/// Phrases that signal the model is unsure of its answer.
+const HEDGES: &[&str] = &[
+    "i think",
+    "i believe",
+    "not sure",
+    "not certain",
+    "it seems",
+    "it appears",
+    "probably",
+    "possibly",
+    "perhaps",
+    "maybe",
+    "might",
+    "could be",
+    "unclear",
+    "hard to say",
+    "as far as i know",
+];
+
+/// Confidence lost per hedge found.
+const HEDGE_PENALTY: f32 = 0.15;
+/// Most confidence hedging alone can take away.
+const MAX_HEDGE_PENALTY: f32 = 0.6;
+
+/// Heuristic confidence (0-1) in `answer`: lowered by each hedge it
+/// contains and, when given, averaged with how well the injected knowledge
+/// supports it.
+pub fn answer_confidence(answer: &str, grounding_support: Option<f32>) -> f32 {
+    let hedges = hedges(answer);
+    let from_language = 1.0 - (hedges as f32 * HEDGE_PENALTY).min(MAX_HEDGE_PENALTY);
+    match grounding_support {
+        Some(support) => (from_language + support.clamp(0.0, 1.0)) / 2.0,
+        None => from_language,
+    }
+}
+
+/// Hedge phrases in `text`, matched on word boundaries.
+fn hedges(text: &str) -> usize {
+    let words: String = text
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '\'' {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let padded = format!(
+        " {} ",
+        words.split_whitespace().collect::<Vec<_>>().join(" ")
+    );
+    HEDGES
+        .iter()
+        .map(|hedge| padded.matches(&format!(" {} ", hedge)).count())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hedging_lowers_confidence() {
+        assert_eq!(answer_confidence("Raise prices by 5%.", None), 1.0);
+        let hedged = "I think it's probably fine, but it might not be.";
+        assert!((answer_confidence(hedged, None) - 0.55).abs() < 1e-6);
+        assert!((answer_confidence("Raise prices.", Some(0.4)) - 0.7).abs() < 1e-6);
+        // "Mighty" isn't "might"
+        assert_eq!(answer_confidence("A mighty plan.", None), 1.0);
+    }
+}
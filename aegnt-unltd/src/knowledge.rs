@@ -0,0 +1,450 @@
+use crate::Intent;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// A retrievable piece of a knowledge document (one paragraph).
+#[derive(Debug, Clone, Serialize)]
+pub struct Chunk {
+    pub id: String,
+    pub source: String,
+    pub text: String,
+    /// Intents this chunk may ground; empty means all intents.
+    pub intents: Vec<Intent>,
+    /// Persona whose answers this chunk may ground; shared by every
+    /// persona when unset.
+    pub persona: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoredChunk {
+    pub chunk: Chunk,
+    /// First-stage retrieval score: share of query terms in the chunk.
+    pub score: f32,
+    /// Cross-encoder score, when the chunk was reranked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
+}
+
+/// A knowledge file left out of the index because it couldn't be read.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub source: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default)]
+pub struct KnowledgeIndex {
+    pub documents: usize,
+    pub chunks: Vec<Chunk>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl KnowledgeIndex {
+    /// Reads every file under `root` and splits it into paragraph chunks.
+    ///
+    /// Unreadable files (including invalid UTF-8) are logged and skipped,
+    /// unless `strict`, in which case the first one fails the build. A
+    /// missing `root` is an empty knowledge base, not a failure.
+    pub async fn build(root: &Path, strict: bool) -> Result<Self, String> {
+        let mut index = Self::default();
+        if matches!(tokio::fs::try_exists(root).await, Ok(false)) {
+            eprintln!(
+                "[knowledge] {} does not exist; serving without knowledge",
+                root.display()
+            );
+            return Ok(index);
+        }
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&dir)
+                .await
+                .map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| format!("cannot read {}: {}", dir.display(), e))?
+            {
+                let path = entry.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                let source = source_name(root, &path);
+                match tokio::fs::read_to_string(&path).await {
+                    Ok(text) => index.add_document(&source, &text),
+                    Err(e) if strict => {
+                        return Err(format!("cannot read {}: {}", path.display(), e));
+                    }
+                    Err(e) => {
+                        eprintln!("[knowledge] skipping {}: {}", path.display(), e);
+                        index.skipped.push(SkippedFile {
+                            source,
+                            error: e.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub(crate) fn add_document(&mut self, source: &str, text: &str) {
+        let (frontmatter, body) = parse_frontmatter(text);
+        let persona = frontmatter.persona.or_else(|| directory_persona(source));
+        self.documents += 1;
+        for (i, paragraph) in body
+            .split("\n\n")
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .enumerate()
+        {
+            self.chunks.push(Chunk {
+                id: format!("{}#{}", source, i),
+                source: source.to_string(),
+                text: paragraph.to_string(),
+                intents: frontmatter.intents.clone(),
+                persona: persona.clone(),
+            });
+        }
+    }
+
+    /// Whether any chunk is scoped to `intent` by name.
+    pub fn scopes(&self, intent: &Intent) -> bool {
+        self.chunks
+            .iter()
+            .any(|chunk| chunk.intents.contains(intent))
+    }
+
+    /// Personas with documents of their own, sorted.
+    pub fn personas(&self) -> Vec<String> {
+        let mut personas: Vec<String> = self
+            .chunks
+            .iter()
+            .filter_map(|c| c.persona.clone())
+            .collect();
+        personas.sort();
+        personas.dedup();
+        personas
+    }
+
+    /// Returns the `top_k` chunks sharing the most terms with `query`,
+    /// restricted to chunks that apply to `intent` and are shared or
+    /// belong to `persona`.
+    pub fn retrieve(
+        &self,
+        query: &str,
+        intent: &Intent,
+        persona: Option<&str>,
+        top_k: usize,
+    ) -> Vec<ScoredChunk> {
+        let terms = terms(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut scored: Vec<ScoredChunk> = self
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.intents.is_empty() || chunk.intents.contains(intent))
+            .filter(|chunk| chunk.persona.is_none() || chunk.persona.as_deref() == persona)
+            .filter_map(|chunk| {
+                let chunk_terms = self::terms(&chunk.text);
+                let hits = terms.iter().filter(|t| chunk_terms.contains(t)).count();
+                (hits > 0).then(|| ScoredChunk {
+                    chunk: chunk.clone(),
+                    score: hits as f32 / terms.len() as f32,
+                    rerank_score: None,
+                })
+            })
+            .collect();
+
+        scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+        scored.truncate(top_k);
+        scored
+    }
+}
+
+/// Share (0-1) of `answer`'s terms that appear in the `grounding` chunks:
+/// a keyword-overlap stand-in for an entailment check.
+pub fn support(answer: &str, grounding: &[ScoredChunk]) -> f32 {
+    let answer_terms = terms(answer);
+    if answer_terms.is_empty() {
+        return 0.0;
+    }
+    let grounding_terms: Vec<String> = grounding
+        .iter()
+        .flat_map(|c| terms(&c.chunk.text))
+        .collect();
+
+    let supported = answer_terms
+        .iter()
+        .filter(|t| grounding_terms.contains(t))
+        .count();
+    supported as f32 / answer_terms.len() as f32
+}
+
+#[derive(Debug, Default)]
+struct Frontmatter {
+    intents: Vec<Intent>,
+    persona: Option<String>,
+}
+
+/// Splits an optional `---` frontmatter block off a document, returning
+/// its `intents:` and `persona:` keys and the remaining body.
+fn parse_frontmatter(text: &str) -> (Frontmatter, &str) {
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return (Frontmatter::default(), text);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (Frontmatter::default(), text);
+    };
+    let value = |key: &str| {
+        rest[..end]
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(key).map(str::trim))
+    };
+
+    let intents = value("intents:")
+        .map(|list| {
+            list.trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .filter_map(
+                    |name| match name.trim().trim_matches(|c| c == '"' || c == '\'') {
+                        "QuickAction" => Some(Intent::QuickAction),
+                        "Strategy" => Some(Intent::Strategy),
+                        "Unknown" => Some(Intent::Unknown),
+                        _ => None,
+                    },
+                )
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let persona = value("persona:")
+        .map(|name| name.trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|name| !name.is_empty());
+
+    let body = rest[end + 4..].trim_start_matches(|c| c != '\n');
+    (Frontmatter { intents, persona }, body)
+}
+
+/// Persona owning a document stored under `personas/<name>/`.
+fn directory_persona(source: &str) -> Option<String> {
+    let mut components = Path::new(source).components();
+    match (components.next(), components.next(), components.next()) {
+        (Some(dir), Some(name), Some(_)) if dir.as_os_str() == "personas" => {
+            Some(name.as_os_str().to_string_lossy().into_owned())
+        }
+        _ => None,
+    }
+}
+
+fn source_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn terms(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.len() > 2)
+        .map(str::to_lowercase)
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexState {
+    #[default]
+    Empty,
+    Indexing,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReindexStatus {
+    pub state: IndexState,
+    pub documents: usize,
+    pub chunks: usize,
+    /// Files left out of the serving index because they couldn't be read.
+    pub skipped: Vec<SkippedFile>,
+    /// Unix seconds when the serving index was last swapped in.
+    pub last_indexed_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+/// Serving knowledge index plus the status of any background rebuild.
+///
+/// Queries keep reading the current index while a rebuild runs; the new
+/// index is swapped in only once it's complete.
+#[derive(Default)]
+pub struct KnowledgeStore {
+    index: RwLock<Arc<KnowledgeIndex>>,
+    status: RwLock<ReindexStatus>,
+}
+
+impl KnowledgeStore {
+    pub async fn current(&self) -> Arc<KnowledgeIndex> {
+        self.index.read().await.clone()
+    }
+
+    pub async fn status(&self) -> ReindexStatus {
+        self.status.read().await.clone()
+    }
+
+    /// Rebuilds the index from `root` in a background task (see
+    /// [`KnowledgeIndex::build`] for `strict`). A rebuild that is already
+    /// running is left alone.
+    pub async fn reindex(self: &Arc<Self>, root: PathBuf, strict: bool) -> Option<JoinHandle<()>> {
+        {
+            let mut status = self.status.write().await;
+            if status.state == IndexState::Indexing {
+                return None;
+            }
+            status.state = IndexState::Indexing;
+        }
+
+        let store = self.clone();
+        Some(tokio::spawn(async move {
+            let result = KnowledgeIndex::build(&root, strict).await;
+            let mut status = store.status.write().await;
+            match result {
+                Ok(index) => {
+                    status.state = IndexState::Ready;
+                    status.documents = index.documents;
+                    status.chunks = index.chunks.len();
+                    status.skipped = index.skipped.clone();
+                    status.last_indexed_at = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .ok()
+                        .map(|d| d.as_secs());
+                    status.last_error = None;
+                    *store.index.write().await = Arc::new(index);
+                }
+                Err(e) => {
+                    // Keep serving whatever index we already had.
+                    status.state = IndexState::Failed;
+                    status.last_error = Some(e);
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retrieve_ranks_by_term_overlap() {
+        let mut index = KnowledgeIndex::default();
+        index.add_document(
+            "pricing.md",
+            "Pricing tiers favour annual plans.\n\nDiscounts need approval.",
+        );
+        index.add_document("hiring.md", "Hiring plans are reviewed quarterly.");
+
+        let hits = index.retrieve("annual pricing plans", &Intent::Strategy, None, 2);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].chunk.id, "pricing.md#0");
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn test_frontmatter_scopes_chunks_to_intents() {
+        let mut index = KnowledgeIndex::default();
+        index.add_document(
+            "code.md",
+            "---\nintents: [QuickAction]\n---\nDeploy plans run nightly.",
+        );
+        index.add_document(
+            "strategy.md",
+            "---\nintents: [Strategy]\n---\nPlans favour growth.",
+        );
+
+        let hits = index.retrieve("plans", &Intent::Strategy, None, 5);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk.source, "strategy.md");
+        assert_eq!(hits[0].chunk.text, "Plans favour growth.");
+    }
+
+    #[test]
+    fn test_persona_scoping() {
+        let mut index = KnowledgeIndex::default();
+        index.add_document("shared.md", "Plans renew yearly.");
+        index.add_document("personas/acme/plans.md", "Acme plans include support.");
+        index.add_document(
+            "globex.md",
+            "---\npersona: globex\n---\nGlobex plans are metered.",
+        );
+        assert_eq!(index.personas(), vec!["acme", "globex"]);
+
+        let sources = |persona| -> Vec<String> {
+            index
+                .retrieve("plans", &Intent::Strategy, persona, 5)
+                .into_iter()
+                .map(|hit| hit.chunk.source)
+                .collect()
+        };
+        assert_eq!(sources(None), vec!["shared.md"]);
+        let mut acme = sources(Some("acme"));
+        acme.sort();
+        assert_eq!(acme, vec!["personas/acme/plans.md", "shared.md"]);
+    }
+
+    #[test]
+    fn test_support_measures_answer_overlap() {
+        let mut index = KnowledgeIndex::default();
+        index.add_document("pricing.md", "Annual plans get a discount.");
+        let grounding = index.retrieve("annual plans", &Intent::Strategy, None, 1);
+
+        assert_eq!(support("Offer annual plans", &grounding), 2.0 / 3.0);
+        assert_eq!(support("Hire more engineers", &grounding), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_files_are_skipped_unless_strict() {
+        let root = std::env::temp_dir().join(format!("aegnt-knowledge-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("good.md"), "Pricing favours annual plans.").unwrap();
+        std::fs::write(root.join("bad.md"), [0xff, 0xfe, 0xfd]).unwrap();
+
+        let index = KnowledgeIndex::build(&root, false).await.unwrap();
+        assert_eq!(index.documents, 1);
+        assert_eq!(index.skipped.len(), 1);
+        assert_eq!(index.skipped[0].source, "bad.md");
+
+        assert!(KnowledgeIndex::build(&root, true).await.is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_root_is_an_empty_index() {
+        let root = std::env::temp_dir().join(format!("aegnt-knowledge-{}", uuid::Uuid::new_v4()));
+        let index = KnowledgeIndex::build(&root, true).await.unwrap();
+        assert_eq!(index.documents, 0);
+        assert!(index.chunks.is_empty());
+
+        // An unreadable root is still a failure
+        let file = root.with_extension("md");
+        std::fs::write(&file, "not a directory").unwrap();
+        assert!(KnowledgeIndex::build(&file, false).await.is_err());
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Fewest samples before a percentile is reported; below this the window
+/// says nothing about the backend's current latency.
+const MIN_SAMPLES: usize = 10;
+
+/// Sliding window of the most recent latency samples for one path.
+pub struct LatencyWindow {
+    samples: Mutex<VecDeque<u64>>,
+    capacity: usize,
+}
+
+impl LatencyWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn record(&self, ms: u64) {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(ms);
+    }
+
+    /// 95th percentile of the window, once it holds enough samples.
+    pub fn p95(&self) -> Option<u64> {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        let mut sorted: Vec<u64> = samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (sorted.len() * 95).div_ceil(100);
+        Some(sorted[rank - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_p95_over_recent_samples() {
+        let window = LatencyWindow::new(20);
+        for ms in 1..MIN_SAMPLES as u64 {
+            window.record(ms);
+        }
+        assert_eq!(window.p95(), None);
+
+        for ms in 1..=20 {
+            window.record(ms * 100);
+        }
+        assert_eq!(window.p95(), Some(1900));
+
+        // Old samples age out of the window
+        for _ in 0..20 {
+            window.record(50);
+        }
+        assert_eq!(window.p95(), Some(50));
+    }
+}
@@ -1,45 +1,998 @@
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub mod actions;
+pub mod adaptive;
+pub mod budget;
+pub mod citations;
+pub mod classifier;
+pub mod client;
+pub mod confidence;
+pub mod control;
+pub mod entities;
+pub mod error;
+pub mod features;
+pub mod hedging;
+pub mod knowledge;
+pub mod latency;
+pub mod prompt;
+pub mod queue;
+pub mod ramp;
+pub mod rate_limit;
+pub mod signing;
+pub mod singleflight;
+pub mod spill;
+#[cfg(feature = "statsd")]
+pub mod statsd;
+pub mod stream;
+pub mod structured;
+pub mod telemetry;
+pub mod tools;
+pub mod usage;
+pub mod voice;
+pub mod watermark;
+pub mod webhook;
+
+use budget::TokenLedger;
+use citations::{CitationStyle, ContextRef};
+use classifier::Classifier;
+use client::{BackendTimeouts, BrainClient, HttpPools, PoolSettings};
+use confidence::{ConfidenceLog, ConfidenceReport};
+use control::ControlCommand;
+use entities::EntityRules;
+use error::{ErrorCategory, Phase, ProcessError};
+use features::{FeatureLog, FeatureLogEntry, FeatureRecord};
+use knowledge::{KnowledgeStore, ReindexStatus, ScoredChunk};
+use latency::LatencyWindow;
+use prompt::{AssembledPrompt, Format, OverflowStrategy, PromptParts, PromptTemplate};
+use singleflight::SingleFlight;
+use stream::{EventSender, StopConditions, StreamEvent, StreamGranularity};
+use telemetry::{ClassificationSink, NoopSink};
+use tools::{Tool, ToolCall};
+use usage::Usage;
+use voice::{InputSource, VoicePreprocessing};
+use watermark::WatermarkStyle;
+use webhook::{ClassificationEvent, Notifier};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Intent {
     QuickAction,
     Strategy,
+    #[default]
     Unknown,
 }
 
+impl Intent {
+    pub const ALL: [Intent; 3] = [Intent::QuickAction, Intent::Strategy, Intent::Unknown];
+}
+
+/// Machine-readable record of how a request was routed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoutingDecision {
+    /// Intent produced by the classifier.
+    pub classified_intent: Intent,
+    /// Intent whose path actually produced the answer.
+    pub final_intent: Intent,
+    /// Human-readable explanation of each routing step.
+    pub reason: String,
+    /// Classifier confidence (0-1) in `classified_intent`; a calibrated
+    /// probability when `calibration` is set.
+    #[serde(default)]
+    pub confidence: f32,
+    /// Calibrated probability of every intent, summing to 1, when
+    /// `calibration` is set.
+    #[serde(default)]
+    pub probabilities: Option<HashMap<Intent, f32>>,
+    /// Moved to a deeper path than classified.
+    pub escalated: bool,
+    /// Moved to a faster path than classified (e.g. after a soft timeout or
+    /// an adaptive downgrade).
+    pub downgraded: bool,
+    /// Routing was dictated by an explicit override rather than classification.
+    pub forced: bool,
+    /// The fast answer looked like a misrouted Strategy request; resend it
+    /// forced to Strategy for a deep answer.
+    #[serde(default)]
+    pub escalation_suggested: bool,
+}
+
+impl RoutingDecision {
+    fn note(&mut self, step: impl AsRef<str>) {
+        if !self.reason.is_empty() {
+            self.reason.push_str("; ");
+        }
+        self.reason.push_str(step.as_ref());
+    }
+}
+
+/// Which execution path a directive takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionPath {
+    Fast,
+    Deep,
+    SubBrain,
+}
+
+/// What [`Brain::process`] would do with a directive, short of calling a
+/// model: classification, routing, grounding and the assembled prompt.
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunReport {
+    pub intent: Intent,
+    pub routing: RoutingDecision,
+    pub path: ExecutionPath,
+    /// Model the prompt would be sent to; unset when forwarded to a sub-brain.
+    pub model: Option<String>,
+    /// Detected input language when the directive would be translated.
+    pub language: Option<String>,
+    /// Chunks retrieved before `min_chunk_score`/`max_injected_chunks`.
+    pub retrieved: usize,
+    /// Chunks that would be injected into the prompt.
+    pub knowledge: Vec<ScoredChunk>,
+    /// Final prompt text after templating; unset when forwarded.
+    pub prompt: Option<String>,
+    /// Parts of the request that couldn't be honored as given.
+    pub warnings: Vec<String>,
+    pub prompt_tokens: usize,
+    pub prompt_trimmed: Vec<String>,
+}
+
+/// Per-request options for [`Brain::process`].
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    /// Route down this intent's path regardless of how the input classifies.
+    pub forced: Option<Intent>,
+    /// Sampling seed; a random one is picked when unset.
+    pub seed: Option<u64>,
+    /// Intent of the previous turn in the conversation, for the
+    /// `continuity_weight` bias.
+    pub previous_intent: Option<Intent>,
+    /// Id to give the response, for callers that need it before the
+    /// response exists; a fresh one is generated when unset.
+    pub request_id: Option<String>,
+    /// Active persona; grounding uses only its own and shared knowledge.
+    pub persona: Option<String>,
+    /// Return the model's output before post-processing in `raw_content`.
+    pub include_raw: bool,
+    /// Report the settings the answer was generated with in
+    /// `effective_config`.
+    pub include_config: bool,
+    /// Earlier answers in the conversation, for `repetition_threshold`.
+    pub recent_answers: Vec<String>,
+    /// Conversation the request belongs to, for `conversation_token_budget`.
+    pub conversation_id: Option<String>,
+    /// An earlier answer to the same input that the new one should differ
+    /// from, when regenerating.
+    pub previous_answer: Option<String>,
+    /// Answer format, overriding `intent_default_format`.
+    pub format: Option<Format>,
+    pub source: InputSource,
+}
+
+/// Settings one answer was generated with: enough to reproduce it, and to
+/// tell whether a config change explains a different output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    /// Path that produced the answer.
+    pub intent: Intent,
+    pub model: String,
+    pub seed: u64,
+    pub persona: Option<String>,
+    /// Chunks retrieved for grounding, before reranking and `max_injected_chunks`.
+    pub knowledge_top_k: usize,
+    pub min_chunk_score: Option<f32>,
+    pub max_injected_chunks: Option<usize>,
+    /// Cross-encoder used to rerank retrieved chunks, when reranking.
+    pub rerank_model: Option<String>,
+    /// Support the answer needed from its grounding, when validated.
+    pub min_grounding_support: Option<f32>,
+    pub max_prompt_tokens: Option<usize>,
+    pub overflow_strategy: OverflowStrategy,
+    pub stop_conditions: Option<StopConditions>,
+    pub citations: Option<CitationStyle>,
+    /// House style the answer was rewritten to, when restyled.
+    pub style_guide: Option<String>,
+    pub format: Option<Format>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Response {
     pub intent: Intent,
     pub system: String,
     pub content: String,
+    /// `content` was cut off at `max_response_bytes`.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Model output before stop conditions, restyling, translation and
+    /// truncation, when requested with `include_raw`.
+    #[serde(default)]
+    pub raw_content: Option<String>,
+    /// Knowledge injected into a cited answer, by citation marker.
+    #[serde(default)]
+    pub context_used: Vec<ContextRef>,
+    /// Model that produced the answer.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Tokens the answering model call consumed.
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    /// Tokens the conversation has left under `conversation_token_budget`.
+    #[serde(default)]
+    pub token_budget_remaining: Option<u64>,
+    /// Short TL;DR of a long deep answer, when `summary_min_chars` is set.
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Next steps pulled out of a deep answer, when `extract_action_items`
+    /// is set.
+    #[serde(default)]
+    pub action_items: Vec<String>,
+    /// Settings the answer was generated with, when requested with
+    /// `include_config`.
+    #[serde(default)]
+    pub effective_config: Option<EffectiveConfig>,
+    /// Whether the injected knowledge supports the answer, when
+    /// `validate_grounding` checked it.
+    #[serde(default)]
+    pub grounded: Option<bool>,
+    /// Estimated confidence (0-1) in the answer itself, when
+    /// `estimate_answer_confidence` is on.
+    #[serde(default)]
+    pub answer_confidence: Option<f32>,
     pub reasoning_trace: Option<String>,
+    /// English answer before translation, when the response was translated.
+    #[serde(default)]
+    pub original_content: Option<String>,
+    /// Detected input language (ISO 639-3) when the response was translated.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Full trace for internal logs, set only when `redact_trace` or
+    /// `trace_by_intent` changed what clients see. Never serialized.
+    #[serde(skip)]
+    pub internal_trace: Option<String>,
     pub latency_ms: u64,
+    #[serde(default)]
+    pub routing: RoutingDecision,
+    /// Hints for getting a better answer when routing was borderline.
+    #[serde(default)]
+    pub suggestions: Vec<String>,
+    /// Parts of the request that couldn't be honored as given, such as an
+    /// unknown persona that was substituted.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    #[serde(default)]
+    pub request_id: String,
+    /// SHA-256 of the directive the response answers.
+    #[serde(default)]
+    pub input_hash: String,
+    /// HMAC over `(request_id, input_hash, content)` when a signing key is set.
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Sampling seed passed to the backend; resend it to reproduce the answer.
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl Response {
+    /// A response carrying only `content`; fill in the rest with struct
+    /// update syntax.
+    pub fn new(intent: Intent, system: impl Into<String>, content: String) -> Self {
+        Self {
+            intent,
+            system: system.into(),
+            content,
+            truncated: false,
+            raw_content: None,
+            context_used: Vec::new(),
+            model: None,
+            usage: None,
+            token_budget_remaining: None,
+            summary: None,
+            action_items: Vec::new(),
+            effective_config: None,
+            grounded: None,
+            answer_confidence: None,
+            reasoning_trace: None,
+            original_content: None,
+            language: None,
+            internal_trace: None,
+            latency_ms: 0,
+            routing: RoutingDecision::default(),
+            suggestions: Vec::new(),
+            warnings: Vec::new(),
+            request_id: String::new(),
+            input_hash: String::new(),
+            signature: None,
+            seed: 0,
+        }
+    }
+}
+
+/// Classifier weights for structural cues in the input. Positive weights
+/// pull toward Strategy, negative toward QuickAction; a cue overrides the
+/// keyword rules when its combined weight beats their confidence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructuralWeights {
+    /// Ends with a question mark.
+    pub question: f32,
+    /// Contains a numbered list (two or more `1.`/`1)` lines).
+    pub numbered_list: f32,
+    /// Contains a fenced code block.
+    pub code_fence: f32,
+    /// Opens with an imperative verb ("fix", "write", ...).
+    pub imperative: f32,
 }
 
+impl Default for StructuralWeights {
+    fn default() -> Self {
+        Self {
+            question: -0.4,
+            numbered_list: 0.8,
+            code_fence: -0.6,
+            imperative: -0.2,
+        }
+    }
+}
+
+/// Temperature scaling that turns raw classifier scores into calibrated
+/// probabilities across every intent. Fit it from labeled examples with
+/// `Brain::fit_calibration`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Calibration {
+    /// Above 1 softens overconfident scores, below 1 sharpens them.
+    pub temperature: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self { temperature: 1.0 }
+    }
+}
+
+impl Calibration {
+    /// Probability of each intent given the classifier picked `intent` with
+    /// raw `confidence`. The remaining mass is spread evenly over the
+    /// other intents, then every share is tempered and renormalized.
+    pub fn probabilities(&self, intent: &Intent, confidence: f32) -> HashMap<Intent, f32> {
+        let confidence = confidence.clamp(0.0, 1.0);
+        let rest = (1.0 - confidence) / (Intent::ALL.len() - 1) as f32;
+        let exponent = 1.0 / self.temperature.max(f32::EPSILON);
+
+        let tempered: Vec<(Intent, f32)> = Intent::ALL
+            .iter()
+            .map(|i| {
+                (
+                    i.clone(),
+                    if i == intent { confidence } else { rest }.powf(exponent),
+                )
+            })
+            .collect();
+        let total: f32 = tempered.iter().map(|(_, p)| p).sum();
+        tempered.into_iter().map(|(i, p)| (i, p / total)).collect()
+    }
+}
+
+/// `Brain::fit_calibration` tries temperatures from one step up to
+/// `CALIBRATION_STEPS` steps.
+const CALIBRATION_STEPS: u32 = 100;
+const CALIBRATION_TEMPERATURE_STEP: f32 = 0.05;
+
+/// One regional replica of a sub-brain.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionalBackend {
+    pub region: String,
+    pub url: String,
+}
+
+/// A sub-brain client, tagged with its region when it's one of several.
+struct SubBrain {
+    region: Option<String>,
+    client: BrainClient,
+}
+
+/// The tunable part of intent classification, for evaluating a proposed
+/// change with `Brain::compare_classifier` before deploying it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassifierConfig {
+    #[serde(default)]
+    pub structural_weights: Option<StructuralWeights>,
+    #[serde(default)]
+    pub calibration: Option<Calibration>,
+    #[serde(default)]
+    pub complexity_threshold: Option<f32>,
+    #[serde(default)]
+    pub entity_rules: Option<EntityRules>,
+}
+
+/// How one classifier labels one input.
+#[derive(Debug, Clone, Serialize)]
+pub struct Classification {
+    pub intent: Intent,
+    pub reason: String,
+    pub confidence: f32,
+    pub probabilities: Option<HashMap<Intent, f32>>,
+}
+
+impl Classification {
+    fn new(
+        (intent, reason, confidence): (Intent, String, f32),
+        calibration: Option<&Calibration>,
+    ) -> Self {
+        let probabilities = calibration.map(|c| c.probabilities(&intent, confidence));
+        let confidence = probabilities.as_ref().map_or(confidence, |p| p[&intent]);
+        Self {
+            intent,
+            reason,
+            confidence,
+            probabilities,
+        }
+    }
+}
+
+/// One input classified under the current and a proposed classifier config.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassificationDiff {
+    pub input: String,
+    pub current: Classification,
+    pub proposed: Classification,
+    /// The proposed config routes this input to a different intent.
+    pub changed: bool,
+}
+
+/// What to do with a fast answer that trips `fast_escalation_length`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EscalationMode {
+    /// Keep the fast answer and flag `escalation_suggested` in the routing.
+    #[default]
+    Suggest,
+    /// Re-run the request on the deep path and return that answer instead.
+    Auto,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BrainConfig {
     pub fast_model: String,
     pub slow_model: String,
+    /// Interchangeable fast models and their selection weights. When set,
+    /// each fast request draws one (weighted, from its seed) in place of
+    /// `fast_model`.
+    #[serde(default)]
+    pub fast_model_pool: HashMap<String, u32>,
+    /// Connection pool settings per sub-brain, by base URL. Every intent
+    /// and region forwarding to a URL shares its pool.
+    #[serde(default)]
+    pub backend_pools: HashMap<String, PoolSettings>,
     pub memory_path: PathBuf,
     pub knowledge_path: PathBuf,
+    /// Knowledge chunks retrieved to ground deep reasoning (default 3).
+    #[serde(default)]
+    pub knowledge_top_k: Option<usize>,
+    /// Fail a knowledge rebuild on the first unreadable file instead of
+    /// skipping it.
+    #[serde(default)]
+    pub strict_knowledge: bool,
+    /// Prompt plus completion tokens one conversation may spend across its
+    /// turns; once spent, requests are answered with a notice instead of a
+    /// model call. Unlimited when unset.
+    #[serde(default)]
+    pub conversation_token_budget: Option<u64>,
+    /// Regenerate once, asking for a different perspective, when an answer's
+    /// word overlap (0-1) with one of the request's `recent_answers` reaches
+    /// this; off when unset.
+    #[serde(default)]
+    pub repetition_threshold: Option<f32>,
+    /// Persona used in place of a requested one with no knowledge in the
+    /// index; without it such requests get shared knowledge only.
+    #[serde(default)]
+    pub fallback_persona: Option<String>,
+    /// Reject requests for unknown personas instead of substituting.
+    #[serde(default)]
+    pub strict_personas: bool,
+    /// Re-score retrieved chunks with `rerank_model` (a cross-encoder) and
+    /// keep the top `knowledge_top_k` of them. Adds a model call per chunk.
+    #[serde(default)]
+    pub rerank: bool,
+    #[serde(default)]
+    pub rerank_model: String,
+    /// Chunks retrieved as rerank candidates (default 10).
+    #[serde(default)]
+    pub rerank_candidates: Option<usize>,
+    /// Check deep answers against the injected knowledge and report
+    /// `grounded` on the response.
+    #[serde(default)]
+    pub validate_grounding: bool,
+    /// Share of answer terms the knowledge must contain to count as
+    /// grounded (default 0.3).
+    #[serde(default)]
+    pub min_grounding_support: Option<f32>,
+    /// Regenerate an ungrounded deep answer once.
+    #[serde(default)]
+    pub regenerate_ungrounded: bool,
+    /// Estimate `answer_confidence` from hedging in the answer and, on the
+    /// deep path, how well the knowledge supports it.
+    #[serde(default)]
+    pub estimate_answer_confidence: bool,
+    /// Retries one request may spend across every retry point (self-check
+    /// regeneration, escalation re-routes, grounding regeneration); once
+    /// spent, the best answer so far is returned. Unlimited when unset.
+    #[serde(default)]
+    pub max_total_retries: Option<u32>,
+    /// Most retrieved chunks injected into the prompt; all of them when unset.
+    #[serde(default)]
+    pub max_injected_chunks: Option<usize>,
+    /// Retrieved chunks scoring below this relevance (0-1) are not injected.
+    #[serde(default)]
+    pub min_chunk_score: Option<f32>,
+    /// Hard cap on answer size; longer content is cut at a UTF-8 boundary
+    /// and marked `truncated`.
+    #[serde(default)]
+    pub max_response_bytes: Option<usize>,
+    /// Appended to truncated content (default `…[truncated]`); set it to
+    /// an empty string to omit the marker.
+    #[serde(default)]
+    pub truncation_marker: Option<String>,
+    /// Cap on the reasoning trace, in characters, in responses and internal
+    /// logs alike; a longer trace is cut and ends with a note saying so.
+    #[serde(default)]
+    pub max_trace_chars: Option<usize>,
+    /// Directives longer than this many tokens are split into chunks of
+    /// about this size, each answered on the fast model, and the partial
+    /// answers synthesized on the deep model; off when unset.
+    #[serde(default)]
+    pub chunk_input_tokens: Option<usize>,
+    /// Token budget for system prompt + knowledge + history + input.
+    #[serde(default)]
+    pub max_prompt_tokens: Option<usize>,
+    /// Tokens the deep path may spend reasoning, whatever the answer uses;
+    /// unbounded when unset.
+    #[serde(default)]
+    pub reasoning_token_budget: Option<usize>,
+    /// Tokens the deep path's final answer may use, whatever reasoning
+    /// used; unbounded when unset.
+    #[serde(default)]
+    pub answer_token_budget: Option<usize>,
+    #[serde(default)]
+    pub overflow_strategy: OverflowStrategy,
+    /// Deadline for the deep path before cutting over to the fast model.
+    #[serde(default)]
+    pub soft_timeout_ms: Option<u64>,
+    /// Abort a stream when no frame arrives for this long. The window
+    /// resets on every frame, so a long but healthy generation completes.
+    #[serde(default)]
+    pub stream_idle_timeout_ms: Option<u64>,
+    /// Ask the fast model to critique its own answer and regenerate once
+    /// if it flags a problem. Adds a model round-trip to the fast path.
+    #[serde(default)]
+    pub fast_self_check: bool,
+    /// Bias (0-1) toward the previous turn's intent. A follow-up keeps that
+    /// intent when the weight beats the confidence of the rule that
+    /// classified it; off when unset.
+    #[serde(default)]
+    pub continuity_weight: Option<f32>,
+    /// Fall back to the built-in rules when a `Classifier` registered with
+    /// `Brain::with_classifier` takes longer than this many ms; unbounded
+    /// when unset.
+    #[serde(default)]
+    pub classification_timeout_ms: Option<u64>,
+    /// Let structural cues (questions, numbered lists, code, imperatives)
+    /// weigh in on classification; keywords only when unset.
+    #[serde(default)]
+    pub structural_weights: Option<StructuralWeights>,
+    /// Route inputs whose estimated complexity (0-1, from length, clauses,
+    /// nested questions and sub-tasks) reaches this to the deep path,
+    /// whatever their keywords say.
+    #[serde(default)]
+    pub complexity_threshold: Option<f32>,
+    /// Let the kinds of entity in the input (money, dates, ...) weigh in
+    /// on classification; off when unset.
+    #[serde(default)]
+    pub entity_rules: Option<EntityRules>,
+    /// Restore punctuation and capitalization in `source: voice`
+    /// directives, for classification only or throughout; off when unset.
+    #[serde(default)]
+    pub voice_preprocessing: Option<VoicePreprocessing>,
+    /// Report classifier confidence as calibrated probabilities.
+    #[serde(default)]
+    pub calibration: Option<Calibration>,
+    /// Send low-confidence Strategy requests down the fast path while the
+    /// deep path's recent p95 latency exceeds this many ms; off when unset.
+    #[serde(default)]
+    pub adaptive_deep_p95_ms: Option<u64>,
+    /// Strategy requests classified below this confidence may be adaptively
+    /// downgraded (default 0.8).
+    #[serde(default)]
+    pub adaptive_max_confidence: Option<f32>,
+    /// Length of each period classifier confidence is histogrammed over
+    /// (default 3600).
+    #[serde(default)]
+    pub confidence_period_secs: Option<u64>,
+    /// Periods of confidence history kept (default 24).
+    #[serde(default)]
+    pub confidence_periods: Option<usize>,
+    /// Tool calls a QuickAction may make before it must answer (default 5).
+    #[serde(default)]
+    pub max_tool_iterations: Option<usize>,
+    /// Run a tool call only when the fast model is at least this sure (0-1)
+    /// the tool is needed, by the confidence the call states or else the
+    /// hedging in its step; otherwise it answers in text.
+    #[serde(default)]
+    pub min_tool_confidence: Option<f32>,
+    /// Fast answers at least this many characters long that contain
+    /// strategy keywords are treated as misrouted; off when unset.
+    #[serde(default)]
+    pub fast_escalation_length: Option<usize>,
+    #[serde(default)]
+    pub fast_escalation: EscalationMode,
+    /// Whether `/process/stream` frames carry single tokens or whole
+    /// sentences.
+    #[serde(default)]
+    pub stream_granularity: StreamGranularity,
+    /// Have deep answers cite the injected knowledge with `[n]` markers,
+    /// rendered in this style; off when unset.
+    #[serde(default)]
+    pub citations: Option<CitationStyle>,
+    /// Watermark generated content with its request id, for provenance
+    /// checks with `watermark::verify`; off when unset. Truncation to
+    /// `max_response_bytes` leaves room for the mark.
+    #[serde(default)]
+    pub watermark: Option<WatermarkStyle>,
+    /// Sent as a `status` frame on streamed requests (e.g. "Analyzing
+    /// strategy…") as soon as they route to the deep path; none when unset.
+    #[serde(default)]
+    pub thinking_message: Option<String>,
+    /// Stop sequences and token caps for generation, by the intent whose
+    /// path answers.
+    #[serde(default)]
+    pub stop_conditions: HashMap<Intent, StopConditions>,
+    /// Strip internal details (knowledge paths and chunk ids, sub-brain
+    /// URLs) from the trace and routing reason returned to clients.
+    #[serde(default)]
+    pub redact_trace: bool,
+    /// Whether clients get a reasoning trace, by the intent whose path
+    /// answered; intents not listed keep their trace.
+    #[serde(default)]
+    pub trace_by_intent: HashMap<Intent, bool>,
+    /// House style guide (e.g. "formal, concise, bulleted"); when set, the
+    /// fast model rewrites every answer to match it.
+    #[serde(default)]
+    pub style_guide: Option<String>,
+    /// Whether `style_guide` applies, by the intent whose path answered;
+    /// intents not listed are restyled.
+    #[serde(default)]
+    pub style_by_intent: HashMap<Intent, bool>,
+    /// Answer format asked of each intent's path unless the request names
+    /// one; no format is asked for intents not listed.
+    #[serde(default)]
+    pub intent_default_format: HashMap<Intent, Format>,
+    /// Deep answers at least this many characters long get a TL;DR from the
+    /// fast model in `summary`; off when unset.
+    #[serde(default)]
+    pub summary_min_chars: Option<usize>,
+    /// Pull numbered steps and imperative bullets out of deep answers into
+    /// `action_items`.
+    #[serde(default)]
+    pub extract_action_items: bool,
+    /// Model used to translate non-English directives to English and the
+    /// answer back to the input language; translation is off when unset.
+    #[serde(default)]
+    pub translation_model: Option<String>,
+    /// Prefix marking a directive as a control command (e.g. `::`), handled
+    /// by the brain instead of a model; control commands are off when unset.
+    #[serde(default)]
+    pub control_prefix: Option<String>,
+    /// Share one computation between concurrent identical requests.
+    #[serde(default)]
+    pub coalesce_requests: bool,
+    /// Key for HMAC-signing responses; unsigned when unset.
+    #[serde(default)]
+    pub signing_key: Option<String>,
+    /// Chat template file per model name, with `{{system}}` and `{{user}}`
+    /// placeholders. Models without one get the plain assembled prompt.
+    #[serde(default)]
+    pub prompt_templates: HashMap<String, PathBuf>,
+    /// JSON file mapping exact inputs to the intent they route to, checked
+    /// before the classifier runs.
+    #[serde(default)]
+    pub classification_overrides: Option<PathBuf>,
+    /// JSONL file each request's classification features and final intent
+    /// are appended to, with later corrections, as classifier training data.
+    #[serde(default)]
+    pub classification_feature_log: Option<PathBuf>,
+    /// Downstream brains (base URLs) that handle specific intents.
+    #[serde(default)]
+    pub sub_brains: HashMap<Intent, String>,
+    /// Connect and read timeouts per sub-brain, by the intent it handles.
+    #[serde(default)]
+    pub sub_brain_timeouts: HashMap<Intent, BackendTimeouts>,
+    /// Regional replicas of a sub-brain in failover order, used in place of
+    /// the intent's `sub_brains` entry. Replicas in `region` go first.
+    #[serde(default)]
+    pub sub_brain_regions: HashMap<Intent, Vec<RegionalBackend>>,
+    /// Region this brain runs in.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Fail over to the next region when one hasn't answered within this
+    /// long; only errors trigger failover when unset.
+    #[serde(default)]
+    pub region_failover_ms: Option<u64>,
+    /// URL notified (fire-and-forget) whenever a request classifies as the
+    /// given intent.
+    #[serde(default)]
+    pub webhooks: HashMap<Intent, String>,
 }
 
 pub struct Brain {
     config: BrainConfig,
     system_prompt: RwLock<String>,
+    prompt_templates: RwLock<HashMap<String, PromptTemplate>>,
+    overrides: RwLock<HashMap<String, Intent>>,
     intent_classifier: IntentClassifier,
+    classifier: Option<Arc<dyn Classifier>>,
+    sub_brains: HashMap<Intent, Vec<SubBrain>>,
+    knowledge: Arc<KnowledgeStore>,
+    inflight: SingleFlight<Result<Response, ProcessError>>,
+    notifier: Notifier,
+    deep_latency: LatencyWindow,
+    confidence: ConfidenceLog,
+    token_ledger: TokenLedger,
+    classification_sink: Arc<dyn ClassificationSink>,
+    feature_log: Option<Arc<FeatureLog>>,
+    tools: Vec<Arc<dyn Tool>>,
 }
 
+const DEFAULT_KNOWLEDGE_TOP_K: usize = 3;
+const DEFAULT_RERANK_CANDIDATES: usize = 10;
+const DEFAULT_MIN_GROUNDING_SUPPORT: f32 = 0.3;
+const DEFAULT_TRUNCATION_MARKER: &str = "…[truncated]";
+const DEFAULT_ADAPTIVE_MAX_CONFIDENCE: f32 = 0.8;
+const DEFAULT_CONFIDENCE_PERIOD_SECS: u64 = 3600;
+const DEFAULT_CONFIDENCE_PERIODS: usize = 24;
+const DEFAULT_MAX_TOOL_ITERATIONS: usize = 5;
+/// Longest summary produced for `summary_min_chars`, in words.
+const SUMMARY_MAX_WORDS: usize = 30;
+/// Recent deep-path latencies kept for adaptive routing.
+const DEEP_LATENCY_WINDOW: usize = 100;
+
 impl Brain {
     pub fn new(config: BrainConfig) -> Self {
+        let mut pools = HttpPools::new(&config.backend_pools);
+        let mut client = |intent: &Intent, url: &str| {
+            let timeouts = config
+                .sub_brain_timeouts
+                .get(intent)
+                .copied()
+                .unwrap_or_default();
+            BrainClient::new(url).with_http(pools.client(url, timeouts))
+        };
+        let mut sub_brains: HashMap<Intent, Vec<SubBrain>> = config
+            .sub_brains
+            .iter()
+            .map(|(intent, url)| {
+                (
+                    intent.clone(),
+                    vec![SubBrain {
+                        region: None,
+                        client: client(intent, url),
+                    }],
+                )
+            })
+            .collect();
+        for (intent, replicas) in config
+            .sub_brain_regions
+            .iter()
+            .filter(|(_, r)| !r.is_empty())
+        {
+            let mut replicas: Vec<&RegionalBackend> = replicas.iter().collect();
+            // Stable, so the configured order holds within each group
+            replicas.sort_by_key(|r| Some(&r.region) != config.region.as_ref());
+            let replicas = replicas
+                .into_iter()
+                .map(|r| SubBrain {
+                    region: Some(r.region.clone()),
+                    client: client(intent, &r.url),
+                })
+                .collect();
+            sub_brains.insert(intent.clone(), replicas);
+        }
+
         Self {
             config: config.clone(),
             system_prompt: RwLock::new(String::new()),
-            intent_classifier: IntentClassifier::new(),
+            prompt_templates: RwLock::new(HashMap::new()),
+            overrides: RwLock::new(HashMap::new()),
+            intent_classifier: IntentClassifier::new(
+                config.structural_weights.clone(),
+                config.entity_rules.clone(),
+                config.complexity_threshold,
+            ),
+            classifier: None,
+            sub_brains,
+            knowledge: Arc::new(KnowledgeStore::default()),
+            inflight: SingleFlight::default(),
+            notifier: Notifier::new(),
+            deep_latency: LatencyWindow::new(DEEP_LATENCY_WINDOW),
+            confidence: ConfidenceLog::new(
+                std::time::Duration::from_secs(
+                    config
+                        .confidence_period_secs
+                        .unwrap_or(DEFAULT_CONFIDENCE_PERIOD_SECS),
+                ),
+                config
+                    .confidence_periods
+                    .unwrap_or(DEFAULT_CONFIDENCE_PERIODS),
+            ),
+            token_ledger: TokenLedger::default(),
+            classification_sink: Arc::new(NoopSink),
+            feature_log: config
+                .classification_feature_log
+                .clone()
+                .map(|path| Arc::new(FeatureLog::new(path))),
+            tools: Vec::new(),
+        }
+    }
+
+    /// Sends every classification decision to `sink`.
+    pub fn with_classification_sink(mut self, sink: Arc<dyn ClassificationSink>) -> Self {
+        self.classification_sink = sink;
+        self
+    }
+
+    /// Classifies with `classifier` in place of the built-in rules.
+    pub fn with_classifier(mut self, classifier: Arc<dyn Classifier>) -> Self {
+        self.classifier = Some(classifier);
+        self
+    }
+
+    /// Registers `tool` for the fast model to call on QuickAction requests.
+    pub fn with_tool(mut self, tool: Arc<dyn Tool>) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    pub fn config(&self) -> &BrainConfig {
+        &self.config
+    }
+
+    pub fn classifier_config(&self) -> ClassifierConfig {
+        ClassifierConfig {
+            structural_weights: self.config.structural_weights.clone(),
+            calibration: self.config.calibration,
+            complexity_threshold: self.config.complexity_threshold,
+            entity_rules: self.config.entity_rules.clone(),
+        }
+    }
+
+    /// Fits a calibration temperature to labeled `(input, intent)` examples
+    /// by minimizing their negative log-likelihood.
+    pub async fn fit_calibration(&self, examples: &[(String, Intent)]) -> Calibration {
+        let mut scored = Vec::with_capacity(examples.len());
+        for (input, label) in examples {
+            let (intent, _, confidence) = self.intent_classifier.explain(input, None).await;
+            scored.push((intent, confidence, label));
+        }
+
+        let nll = |calibration: &Calibration| -> f32 {
+            scored
+                .iter()
+                .map(|(intent, confidence, label)| {
+                    -calibration.probabilities(intent, *confidence)[*label]
+                        .max(1e-6)
+                        .ln()
+                })
+                .sum()
+        };
+        (1..=CALIBRATION_STEPS)
+            .map(|step| Calibration {
+                temperature: step as f32 * CALIBRATION_TEMPERATURE_STEP,
+            })
+            .min_by(|a, b| nll(a).total_cmp(&nll(b)))
+            .unwrap_or_default()
+    }
+
+    /// Classifies each input under both the current and `proposed`
+    /// classifier config, without processing anything.
+    pub async fn compare_classifier(
+        &self,
+        proposed: &ClassifierConfig,
+        inputs: &[String],
+    ) -> Vec<ClassificationDiff> {
+        let candidate = IntentClassifier::new(
+            proposed.structural_weights.clone(),
+            proposed.entity_rules.clone(),
+            proposed.complexity_threshold,
+        );
+        let mut diffs = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let current = Classification::new(
+                self.intent_classifier.explain(input, None).await,
+                self.config.calibration.as_ref(),
+            );
+            let proposed = Classification::new(
+                candidate.explain(input, None).await,
+                proposed.calibration.as_ref(),
+            );
+            diffs.push(ClassificationDiff {
+                input: input.clone(),
+                changed: current.intent != proposed.intent,
+                current,
+                proposed,
+            });
+        }
+        diffs
+    }
+
+    /// Rebuilds the knowledge index from `knowledge_path` in the background.
+    /// Requests keep using the previous index until the rebuild finishes.
+    pub async fn reindex_knowledge(&self) -> Option<tokio::task::JoinHandle<()>> {
+        self.knowledge
+            .reindex(
+                self.config.knowledge_path.clone(),
+                self.config.strict_knowledge,
+            )
+            .await
+    }
+
+    pub async fn knowledge_status(&self) -> ReindexStatus {
+        self.knowledge.status().await
+    }
+
+    /// Classifier confidence histograms per intent over recent periods.
+    pub fn confidence_report(&self) -> ConfidenceReport {
+        self.confidence.report()
+    }
+
+    /// Checks the requested persona against the index, substituting
+    /// `fallback_persona` (or shared knowledge only) for an unknown one and
+    /// returning a warning saying so. Fails instead under `strict_personas`.
+    async fn resolve_persona(
+        &self,
+        options: &ProcessOptions,
+    ) -> Result<(ProcessOptions, Vec<String>), String> {
+        let Some(requested) = options.persona.as_deref() else {
+            return Ok((options.clone(), Vec::new()));
+        };
+        let known = self.personas().await;
+        if known.iter().any(|p| p == requested) {
+            return Ok((options.clone(), Vec::new()));
         }
+        if self.config.strict_personas {
+            return Err(format!(
+                "unknown persona '{}'; expected one of: {}",
+                requested,
+                known.join(", ")
+            ));
+        }
+
+        let fallback = self.config.fallback_persona.clone();
+        let warning = match &fallback {
+            Some(fallback) => format!(
+                "unknown persona '{}'; used '{}' instead",
+                requested, fallback
+            ),
+            None => format!(
+                "unknown persona '{}'; used shared knowledge only",
+                requested
+            ),
+        };
+        Ok((
+            ProcessOptions {
+                persona: fallback,
+                ..options.clone()
+            },
+            vec![warning],
+        ))
+    }
+
+    /// Personas with knowledge of their own in the serving index.
+    pub async fn personas(&self) -> Vec<String> {
+        self.knowledge.current().await.personas()
     }
 
     pub async fn load_system_prompt(&self, path: &PathBuf) -> Result<(), String> {
@@ -53,111 +1006,3658 @@ impl Brain {
         Ok(())
     }
 
-    pub async fn process_directive(&self, input: &str) -> Response {
-        let start = std::time::Instant::now();
-        
-        // 1. Classification (System 1 - The Cortex)
-        let intent = self.intent_classifier.classify(input).await;
-        
-        // 2. Route to appropriate system
-        let (system, content, reasoning_trace) = match intent {
-            Intent::QuickAction => {
-                // System 1: Fast, local model
-                self.fast_execute(input).await
-            }
-            Intent::Strategy => {
-                // System 2: Deep reasoning with grounding
-                self.deep_reason(input).await
-            }
-            Intent::Unknown => {
-                // Default to System 1 for safety
-                self.fast_execute(input).await
-            }
+    /// Loads every configured `prompt_templates` file.
+    pub async fn load_prompt_templates(&self) -> Result<(), String> {
+        let mut templates = HashMap::new();
+        for (model, path) in &self.config.prompt_templates {
+            let text = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("template for {}: {}", model, e))?;
+            templates.insert(model.clone(), PromptTemplate { text });
+        }
+
+        *self.prompt_templates.write().await = templates;
+
+        Ok(())
+    }
+
+    /// Loads the `classification_overrides` table, replacing the current
+    /// one, and returns how many inputs it covers.
+    pub async fn load_classification_overrides(&self) -> Result<usize, String> {
+        let Some(path) = &self.config.classification_overrides else {
+            return Ok(0);
         };
-        
-        let latency_ms = start.elapsed().as_millis() as u64;
-        
-        Response {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| format!("classification overrides: {}", e))?;
+        let table: HashMap<String, Intent> =
+            serde_json::from_str(&text).map_err(|e| format!("classification overrides: {}", e))?;
+        let table: HashMap<String, Intent> = table
+            .into_iter()
+            .map(|(input, intent)| (input.trim().to_string(), intent))
+            .collect();
+
+        let count = table.len();
+        *self.overrides.write().await = table;
+        Ok(count)
+    }
+
+    /// Records that request `request_id` should have been classified as
+    /// `intent`, labelling its entry in `classification_feature_log`.
+    pub async fn record_correction(&self, request_id: &str, intent: Intent) -> Result<(), String> {
+        let Some(log) = &self.feature_log else {
+            return Err("classification_feature_log is not configured".to_string());
+        };
+        log.append(&FeatureLogEntry::Correction {
+            request_id: request_id.to_string(),
             intent,
-            system,
-            content,
-            reasoning_trace,
-            latency_ms,
-        }
+        })
+        .await
     }
 
-    async fn fast_execute(&self, input: &str) -> (String, String, Option<String>) {
-        // System 1: < 200ms, local Llama via Ollama
-        // For now, return mock response
-        (
-            "cortex".to_string(),
-            format!("[FAST] Processed: {}", input),
-            None,
-        )
+    pub async fn process_directive(&self, input: &str) -> Result<Response, ProcessError> {
+        self.process_directive_with_context(input, None).await
     }
 
-    async fn deep_reason(&self, input: &str) -> (String, String, Option<String>) {
-        // System 2: Full reasoning with grounding pass
-        let reasoning = format!("[DEEP] Analyzing strategy for: {}", input);
-        
-        // Grounding pass would happen here
-        let trace = Some(format!(
-            "1. Loaded knowledge from {:?}\n2. Grounding against constitution\n3. Generated plan",
-            self.config.knowledge_path
+    /// Processes a directive extracted from a structured (JSON) input.
+    ///
+    /// Classification runs only on the text at `path`; the whole document is
+    /// passed along as context for execution.
+    pub async fn process_structured(
+        &self,
+        raw: &str,
+        path: &str,
+    ) -> Result<Response, ProcessError> {
+        let (directive, doc) = structured::extract_directive(raw, path)
+            .map_err(|e| ProcessError::new(Phase::Request, ErrorCategory::InvalidRequest, e))?;
+        self.process_directive_with_context(&directive, Some(&doc))
+            .await
+    }
+
+    pub async fn process_directive_with_context(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+    ) -> Result<Response, ProcessError> {
+        self.process(input, context, &ProcessOptions::default())
+            .await
+    }
+
+    pub async fn process(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        options: &ProcessOptions,
+    ) -> Result<Response, ProcessError> {
+        if !self.config.coalesce_requests {
+            return self.execute(input, context, options, None).await;
+        }
+
+        // Requests differing only in their id share an answer, each under
+        // its own id
+        let request_id = options
+            .request_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let options = ProcessOptions {
+            request_id: None,
+            ..options.clone()
+        };
+        let key = signing::input_hash(&format!(
+            "{}\n{}\n{:?}",
+            input,
+            context.map(Value::to_string).unwrap_or_default(),
+            options
         ));
-        
-        (
-            "deep_mind".to_string(),
-            reasoning,
-            trace,
-        )
+        let leader = ProcessOptions {
+            request_id: Some(request_id.clone()),
+            ..options
+        };
+        let (result, shared) = self
+            .inflight
+            .run(key, || self.execute(input, context, &leader, None))
+            .await;
+
+        match result {
+            Ok(response) if shared => {
+                let mut response = self.reissue(response, request_id);
+                response
+                    .routing
+                    .note("coalesced with an identical in-flight request");
+                Ok(response)
+            }
+            Err(e) if shared => Err(e.with_request_id(&request_id)),
+            result => result,
+        }
     }
-}
 
-struct IntentClassifier {
-    // Lightweight classifier for fast intent detection
-}
+    /// Processes a directive, streaming reasoning steps and answer tokens to
+    /// `events`, then a final `Done` (or `Error`) frame.
+    ///
+    /// Execution backends are mocked and return whole answers, so frames are
+    /// emitted once generation completes rather than token-by-token.
+    pub async fn process_streaming(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        options: &ProcessOptions,
+        events: EventSender,
+    ) {
+        let Some(ms) = self.config.stream_idle_timeout_ms else {
+            return self.stream_response(input, context, options, events).await;
+        };
 
-impl IntentClassifier {
-    fn new() -> Self {
-        Self {}
+        let (generated, mut frames) = tokio::sync::mpsc::channel(64);
+        // Finishing the generation drops `generated`, which ends the relay
+        let generate = async {
+            self.stream_response(input, context, options, generated)
+                .await;
+            std::future::pending::<()>().await
+        };
+        let relay = async {
+            loop {
+                match tokio::time::timeout(std::time::Duration::from_millis(ms), frames.recv())
+                    .await
+                {
+                    Ok(Some(event)) => {
+                        if events.send(event).await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => return,
+                    Err(_) => {
+                        let stalled = format!("generation stalled: nothing received for {}ms", ms);
+                        let _ = events.send(StreamEvent::Error(stalled)).await;
+                        return;
+                    }
+                }
+            }
+        };
+        tokio::select! {
+            () = generate => {}
+            () = relay => {}
+        }
     }
 
-    async fn classify(&self, input: &str) -> Intent {
-        // Simple keyword-based classification
-        // In production: use a tiny local model
-        let input_lower = input.to_lowercase();
-        
-        if input_lower.contains("plan") 
-            || input_lower.contains("strategy") 
-            || input_lower.contains("analyze")
-            || input_lower.contains("build architecture")
-            || input_lower.contains("design") {
-            Intent::Strategy
-        } else if input_lower.contains("what")
-            || input_lower.contains("how")
-            || input_lower.len() < 50 {
-            Intent::QuickAction
+    async fn stream_response(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        options: &ProcessOptions,
+        events: EventSender,
+    ) {
+        match self.execute(input, context, options, Some(&events)).await {
+            Ok(response) => {
+                if stream::emit_response(&events, &response, self.config.stream_granularity)
+                    .await
+                    .is_ok()
+                {
+                    let _ = events.send(StreamEvent::Done(Box::new(response))).await;
+                }
+            }
+            Err(e) => {
+                let _ = events.send(StreamEvent::Error(e.to_string())).await;
+            }
+        }
+    }
+
+    /// Runs the pipeline; `events` receives progress frames (such as the
+    /// `thinking_message` status) while streaming.
+    async fn execute(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        options: &ProcessOptions,
+        events: Option<&EventSender>,
+    ) -> Result<Response, ProcessError> {
+        let start = std::time::Instant::now();
+        let original_input = input;
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut retries = RetryBudget(self.config.max_total_retries);
+        let request_id = options
+            .request_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        // Control directives are handled here rather than sent to a model
+        let prefix = self.config.control_prefix.as_deref();
+        if let Some(command) = prefix.and_then(|prefix| control::parse(input, prefix)) {
+            let invalid = |e| ProcessError::new(Phase::Request, ErrorCategory::InvalidRequest, e);
+            let command = command
+                .map_err(invalid)
+                .map_err(|e| e.with_request_id(&request_id))?;
+            let content = self.run_control(command).await.map_err(|e| {
+                ProcessError::new(Phase::Request, ErrorCategory::Internal, e)
+                    .with_request_id(&request_id)
+            })?;
+            return Ok(self.seal(
+                Response {
+                    latency_ms: start.elapsed().as_millis() as u64,
+                    routing: RoutingDecision {
+                        reason: "control directive".to_string(),
+                        ..Default::default()
+                    },
+                    request_id,
+                    input_hash: signing::input_hash(input),
+                    seed,
+                    ..Response::new(Intent::Unknown, "control", content)
+                },
+                &[],
+            ));
+        }
+
+        let (options, warnings) = self.resolve_persona(options).await.map_err(|e| {
+            ProcessError::new(Phase::Request, ErrorCategory::InvalidRequest, e)
+                .with_request_id(&request_id)
+        })?;
+        let options = &options;
+
+        let budget = self
+            .config
+            .conversation_token_budget
+            .zip(options.conversation_id.as_deref());
+        if let Some((limit, conversation)) = budget {
+            if self.token_ledger.spent(conversation) >= limit {
+                return Ok(self.seal(
+                    Response {
+                        token_budget_remaining: Some(0),
+                        latency_ms: start.elapsed().as_millis() as u64,
+                        routing: RoutingDecision {
+                            reason: "conversation token budget spent".to_string(),
+                            ..Default::default()
+                        },
+                        warnings,
+                        request_id,
+                        input_hash: signing::input_hash(input),
+                        seed,
+                        ..Response::new(
+                            Intent::Unknown,
+                            "budget_exhausted",
+                            format!("This conversation has used its {}-token budget.", limit),
+                        )
+                    },
+                    &[],
+                ));
+            }
+        }
+
+        let restored = self.restored_transcript(input, options, VoicePreprocessing::Restore);
+        let input = restored.as_deref().unwrap_or(input);
+
+        // 0. Reason in English: translate foreign-language directives first
+        let language = self.foreign_language(input);
+        let english_input;
+        let input = match &language {
+            Some(lang) => {
+                english_input = self.translate(input, lang, "eng").await;
+                english_input.as_str()
+            }
+            None => input,
+        };
+
+        // 1. Classification (System 1 - The Cortex)
+        let (intent, mut routing) = self.route(input, options).await;
+        // Overrides aren't the classifier's call
+        if !self.overrides.read().await.contains_key(input.trim()) {
+            self.confidence
+                .record(&routing.classified_intent, routing.confidence);
+        }
+        let sink = self.classification_sink.clone();
+        let event = ClassificationEvent::new(&request_id, &routing, original_input);
+        tokio::spawn(async move { sink.record(event).await });
+        if let Some(log) = self.feature_log.clone() {
+            let entry =
+                FeatureLogEntry::Features(FeatureRecord::extract(&request_id, input, &routing));
+            tokio::spawn(async move {
+                if let Err(e) = log.append(&entry).await {
+                    eprintln!("[features] {}", e);
+                }
+            });
+        }
+
+        // Fill the deep path's wait for a first token
+        if let (Some(events), Some(message)) = (events, &self.config.thinking_message) {
+            if routing.final_intent == Intent::Strategy {
+                let _ = events.send(StreamEvent::Status(message.clone())).await;
+            }
+        }
+
+        // Hand off to a specialised sub-brain if one owns this intent
+        if let Some(replicas) = self.sub_brains.get(&intent) {
+            let mut response = self
+                .forward(replicas, input, seed, request_id.clone(), routing, start)
+                .await
+                .map_err(|e| e.with_request_id(&request_id))?;
+            response.token_budget_remaining = self.charge_budget(budget, response.usage.as_ref());
+            self.announce(original_input, &response);
+            return Ok(response);
+        }
+
+        // Over-length directives: answer each chunk on the fast model, then
+        // synthesize the partial answers on the deep path
+        let chunks = match self.config.chunk_input_tokens {
+            Some(max) if !routing.forced && prompt::estimate_tokens(input) > max => {
+                prompt::chunk(input, max)
+            }
+            _ => Vec::new(),
+        };
+        let synthesis;
+        let input = if chunks.len() > 1 {
+            synthesis = self.map_chunks(&chunks, context, seed).await;
+            if routing.final_intent != Intent::Strategy {
+                routing.escalated = true;
+                routing.final_intent = Intent::Strategy;
+            }
+            routing.note(format!(
+                "input split into {} chunks, partial answers synthesized on deep path",
+                chunks.len()
+            ));
+            synthesis.as_str()
         } else {
-            Intent::Unknown
+            input
+        };
+
+        // 2. Retrieve grounding knowledge for the deep path, or for another
+        // path that has knowledge of its own
+        let path = routing.final_intent.clone();
+        let (mut grounding, mut retrieved) = self.grounding(input, &path, options).await;
+
+        // 3. Assemble the prompt within the configured budget
+        let prompt_too_large = |e| {
+            ProcessError::new(Phase::Prompt, ErrorCategory::PromptTooLarge, e)
+                .with_request_id(&request_id)
+        };
+        let (_, mut prompt) = self
+            .build_prompt(input, &path, &grounding, options, seed)
+            .await
+            .map_err(prompt_too_large)?;
+
+        // 4. Route to appropriate system
+        let (mut system, mut content, mut reasoning_trace) = self
+            .generate(
+                &prompt,
+                context,
+                &grounding,
+                seed,
+                &mut routing,
+                &mut retries,
+            )
+            .await;
+
+        // Long, strategy-looking fast answers were probably misrouted
+        let escalation = self.config.fast_escalation_length.filter(|_| {
+            routing.final_intent == Intent::QuickAction && !routing.forced && !routing.downgraded
+        });
+        if let Some(marker) = escalation.and_then(|min_len| strategy_marker(&content, min_len)) {
+            match self.config.fast_escalation {
+                EscalationMode::Auto if !retries.take() => {
+                    routing.escalation_suggested = true;
+                    routing.note(format!(
+                        "fast answer is long and mentions '{}'; re-route skipped, retry budget exhausted",
+                        marker
+                    ));
+                }
+                EscalationMode::Suggest => {
+                    routing.escalation_suggested = true;
+                    routing.note(format!(
+                        "fast answer is long and mentions '{}'; deep path suggested",
+                        marker
+                    ));
+                }
+                EscalationMode::Auto => {
+                    routing.escalated = true;
+                    routing.final_intent = Intent::Strategy;
+                    routing.note(format!(
+                        "fast answer is long and mentions '{}'; re-routed to deep path",
+                        marker
+                    ));
+                    (grounding, retrieved) =
+                        self.grounding(input, &Intent::Strategy, options).await;
+                    (_, prompt) = self
+                        .build_prompt(input, &Intent::Strategy, &grounding, options, seed)
+                        .await
+                        .map_err(prompt_too_large)?;
+                    (system, content, reasoning_trace) = self
+                        .generate(
+                            &prompt,
+                            context,
+                            &grounding,
+                            seed,
+                            &mut routing,
+                            &mut retries,
+                        )
+                        .await;
+                }
+            }
+        }
+
+        // Don't hand back an answer the conversation already has
+        let repeated = self.config.repetition_threshold.and_then(|threshold| {
+            let closest = options
+                .recent_answers
+                .iter()
+                .chain(&options.previous_answer)
+                .map(|recent| word_overlap(&content, recent))
+                .max_by(f32::total_cmp)?;
+            (closest >= threshold).then_some((closest, threshold))
+        });
+        if let Some((overlap, threshold)) = repeated {
+            if retries.take() {
+                let mut reworded = prompt.clone();
+                for text in [&mut reworded.text, &mut reworded.user] {
+                    push_section(text, NEW_PERSPECTIVE_INSTRUCTION);
+                }
+                (system, content, reasoning_trace) = self
+                    .generate(
+                        &reworded,
+                        context,
+                        &grounding,
+                        seed.wrapping_add(1),
+                        &mut routing,
+                        &mut retries,
+                    )
+                    .await;
+                push_trace(
+                    &mut reasoning_trace,
+                    format!("Repetition check: overlap {:.2} with a recent answer (threshold {}); regenerated once for a different perspective", overlap, threshold),
+                );
+            } else {
+                push_trace(
+                    &mut reasoning_trace,
+                    format!("Repetition check: overlap {:.2} with a recent answer (threshold {}); retry budget exhausted", overlap, threshold),
+                );
+            }
+        }
+
+        // Check the deep answer is supported by the injected knowledge
+        let mut grounded = None;
+        let mut grounding_support = None;
+        if self.config.validate_grounding
+            && routing.final_intent == Intent::Strategy
+            && !grounding.is_empty()
+        {
+            let threshold = self
+                .config
+                .min_grounding_support
+                .unwrap_or(DEFAULT_MIN_GROUNDING_SUPPORT);
+            let mut support = knowledge::support(&content, &grounding);
+            if support < threshold && self.config.regenerate_ungrounded {
+                if retries.take() {
+                    (system, content, reasoning_trace) = self
+                        .deep_reason(input, context, &grounding, seed.wrapping_add(1))
+                        .await;
+                    push_trace(
+                        &mut reasoning_trace,
+                        format!(
+                            "Grounding check: support {:.2} below {}; regenerated once",
+                            support, threshold
+                        ),
+                    );
+                    support = knowledge::support(&content, &grounding);
+                } else {
+                    push_trace(
+                        &mut reasoning_trace,
+                        format!(
+                            "Grounding check: support {:.2} below {}; retry budget exhausted",
+                            support, threshold
+                        ),
+                    );
+                }
+            }
+            grounded = Some(support >= threshold);
+            grounding_support = Some(support);
+            push_trace(
+                &mut reasoning_trace,
+                format!(
+                    "Grounding check: support {:.2} (threshold {})",
+                    support, threshold
+                ),
+            );
+        }
+
+        let answer_confidence = self.config.estimate_answer_confidence.then(|| {
+            let support = grounding_support.or_else(|| {
+                (routing.final_intent == Intent::Strategy && !grounding.is_empty())
+                    .then(|| knowledge::support(&content, &grounding))
+            });
+            hedging::answer_confidence(&content, support)
+        });
+
+        if retrieved > 0 {
+            push_trace(
+                &mut reasoning_trace,
+                if self.config.rerank {
+                    format!(
+                        "Knowledge: retrieved {} chunk(s), reranked with {}, injected {}",
+                        retrieved,
+                        self.config.rerank_model,
+                        grounding.len()
+                    )
+                } else {
+                    format!(
+                        "Knowledge: retrieved {} chunk(s), injected {}",
+                        retrieved,
+                        grounding.len()
+                    )
+                },
+            );
+        }
+
+        if chunks.len() > 1 {
+            push_trace(
+                &mut reasoning_trace,
+                format!("Map-reduce: {} chunk(s) answered on the fast model, synthesized on the deep model", chunks.len()),
+            );
+        }
+
+        if !prompt.trimmed.is_empty() {
+            push_trace(
+                &mut reasoning_trace,
+                format!(
+                    "Prompt trimmed to fit budget: {}",
+                    prompt.trimmed.join(", ")
+                ),
+            );
+        }
+
+        let model = self.model_for(&routing.final_intent, seed);
+
+        let raw_content = options.include_raw.then(|| content.clone());
+
+        if let Some(stop) = self.config.stop_conditions.get(&routing.final_intent) {
+            if let Some(reason) = stop.apply(&mut content) {
+                push_trace(
+                    &mut reasoning_trace,
+                    format!("Generation stopped at {}", reason),
+                );
+            }
+        }
+
+        let mut context_used = Vec::new();
+        if let Some(style) = self.config.citations {
+            if routing.final_intent == Intent::Strategy && !grounding.is_empty() {
+                let (rendered, refs, invalid) = citations::render(&content, &grounding, style);
+                content = rendered;
+                context_used = refs;
+                if !invalid.is_empty() {
+                    push_trace(
+                        &mut reasoning_trace,
+                        format!(
+                            "Citations: dropped marker(s) matching no injected chunk: {}",
+                            invalid.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+
+        // Rewrite to the house style so every backend sounds the same
+        let style_guide = self
+            .config
+            .style_guide
+            .as_ref()
+            .filter(|_| self.config.style_by_intent.get(&routing.final_intent) != Some(&false));
+        if let Some(guide) = style_guide {
+            content = self.restyle(&content, guide).await;
+            push_trace(
+                &mut reasoning_trace,
+                "Restyled to house style via fast model".to_string(),
+            );
+        }
+
+        // Give long strategies a gist to read first
+        let mut summary = None;
+        if let Some(min_chars) = self.config.summary_min_chars {
+            if routing.final_intent == Intent::Strategy && content.chars().count() >= min_chars {
+                summary = Some(self.fast_summarize(&content).await);
+            }
+        }
+
+        let mut action_items = Vec::new();
+        if self.config.extract_action_items && routing.final_intent == Intent::Strategy {
+            action_items = actions::extract(&content);
+        }
+
+        // 5. Translate the answer back into the input language
+        let mut original_content = None;
+        if let Some(lang) = &language {
+            let translated = self.translate(&content, "eng", lang).await;
+            original_content = Some(std::mem::replace(&mut content, translated));
+            if let Some(gist) = summary {
+                summary = Some(self.translate(&gist, "eng", lang).await);
+            }
+            if !action_items.is_empty() {
+                let translated = self.translate(&action_items.join("\n"), "eng", lang).await;
+                action_items = translated.lines().map(str::to_string).collect();
+            }
+            push_trace(
+                &mut reasoning_trace,
+                format!("Translated eng <-> {} via translation model", lang),
+            );
         }
+
+        // Backends are mocked and report no usage of their own
+        let usage = Usage::estimate(prompt.tokens, &content);
+        let token_budget_remaining = self.charge_budget(budget, Some(&usage));
+        let effective_config = options.include_config.then(|| EffectiveConfig {
+            intent: routing.final_intent.clone(),
+            model: model.clone(),
+            seed,
+            persona: options.persona.clone(),
+            knowledge_top_k: self
+                .config
+                .knowledge_top_k
+                .unwrap_or(DEFAULT_KNOWLEDGE_TOP_K),
+            min_chunk_score: self.config.min_chunk_score,
+            max_injected_chunks: self.config.max_injected_chunks,
+            rerank_model: self.config.rerank.then(|| self.config.rerank_model.clone()),
+            min_grounding_support: self.config.validate_grounding.then(|| {
+                self.config
+                    .min_grounding_support
+                    .unwrap_or(DEFAULT_MIN_GROUNDING_SUPPORT)
+            }),
+            max_prompt_tokens: self.config.max_prompt_tokens,
+            overflow_strategy: self.config.overflow_strategy,
+            stop_conditions: self
+                .config
+                .stop_conditions
+                .get(&routing.final_intent)
+                .cloned(),
+            citations: self.config.citations,
+            style_guide: style_guide.cloned(),
+            format: self.format_for(&path, options),
+        });
+
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let mut sensitive = vec![format!("{:?}", self.config.knowledge_path)];
+        sensitive.extend(grounding.iter().map(|c| c.chunk.id.clone()));
+
+        let response = self.seal_generated(
+            Response {
+                raw_content,
+                context_used,
+                model: Some(model),
+                usage: Some(usage),
+                token_budget_remaining,
+                summary,
+                action_items,
+                effective_config,
+                grounded,
+                answer_confidence,
+                reasoning_trace,
+                original_content,
+                language,
+                latency_ms,
+                suggestions: suggestions(&routing),
+                warnings,
+                routing,
+                request_id,
+                input_hash: signing::input_hash(original_input),
+                seed,
+                ..Response::new(intent, system, content)
+            },
+            &sensitive,
+        );
+        self.announce(original_input, &response);
+        Ok(response)
     }
-}
 
-pub type SharedBrain = Arc<Brain>;
+    /// Fires the webhook configured for the request's classified intent.
+    fn announce(&self, input: &str, response: &Response) {
+        let intent = &response.routing.classified_intent;
+        if let Some(url) = self.config.webhooks.get(intent) {
+            self.notifier.fire(
+                url,
+                ClassificationEvent::new(&response.request_id, &response.routing, input),
+            );
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Runs a control command, returning the confirmation to send back.
+    async fn run_control(&self, command: ControlCommand) -> Result<String, String> {
+        match command {
+            ControlCommand::ReindexKnowledge => Ok(match self.reindex_knowledge().await {
+                Some(_) => "Knowledge reindex started".to_string(),
+                None => "Knowledge reindex already running".to_string(),
+            }),
+            ControlCommand::ReloadTemplates => {
+                self.load_prompt_templates().await?;
+                Ok(format!(
+                    "Reloaded {} prompt template(s)",
+                    self.prompt_templates.read().await.len()
+                ))
+            }
+            ControlCommand::ReloadOverrides => Ok(format!(
+                "Reloaded {} classification override(s)",
+                self.load_classification_overrides().await?
+            )),
+            ControlCommand::Status => {
+                serde_json::to_string(&self.knowledge_status().await).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Reports what `process` would do with `input` without calling any
+    /// model. Foreign-language input is classified as given, since
+    /// translating it would itself need a model call.
+    pub async fn dry_run(
+        &self,
+        input: &str,
+        options: &ProcessOptions,
+    ) -> Result<DryRunReport, String> {
+        let (options, warnings) = self.resolve_persona(options).await?;
+        let options = &options;
+        let restored = self.restored_transcript(input, options, VoicePreprocessing::Restore);
+        let input = restored.as_deref().unwrap_or(input);
+        let language = self.foreign_language(input);
+        let (intent, routing) = self.route(input, options).await;
+
+        if self.sub_brains.contains_key(&intent) {
+            return Ok(DryRunReport {
+                intent,
+                routing,
+                path: ExecutionPath::SubBrain,
+                model: None,
+                language,
+                retrieved: 0,
+                knowledge: Vec::new(),
+                prompt: None,
+                warnings,
+                prompt_tokens: 0,
+                prompt_trimmed: Vec::new(),
+            });
+        }
+
+        let (grounding, retrieved) = self.grounding(input, &routing.final_intent, options).await;
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let (model, prompt) = self
+            .build_prompt(input, &routing.final_intent, &grounding, options, seed)
+            .await?;
+
+        Ok(DryRunReport {
+            path: match routing.final_intent {
+                Intent::Strategy => ExecutionPath::Deep,
+                _ => ExecutionPath::Fast,
+            },
+            intent,
+            routing,
+            model: Some(model),
+            language,
+            retrieved,
+            knowledge: grounding,
+            prompt: Some(prompt.text),
+            warnings,
+            prompt_tokens: prompt.tokens,
+            prompt_trimmed: prompt.trimmed,
+        })
+    }
+
+    /// Classifies `input` and applies any forced override or adaptive
+    /// downgrade, returning the request's intent and the routing decision so
+    /// far; `final_intent` is the path that will run locally.
+    async fn route(&self, input: &str, options: &ProcessOptions) -> (Intent, RoutingDecision) {
+        let continuity = options
+            .previous_intent
+            .as_ref()
+            .zip(self.config.continuity_weight);
+        let overridden = self.overrides.read().await.get(input.trim()).cloned();
+        let restored = self.restored_transcript(input, options, VoicePreprocessing::Classify);
+        let classified = restored.as_deref().unwrap_or(input);
+        let mut timed_out = None;
+        let classification = match overridden {
+            Some(intent) => Classification {
+                intent,
+                reason: "matched the classification override table".to_string(),
+                confidence: 1.0,
+                probabilities: None,
+            },
+            None => {
+                let custom = match (&self.classifier, self.config.classification_timeout_ms) {
+                    (Some(classifier), Some(ms)) => {
+                        let classify = classifier.classify(classified);
+                        let explained =
+                            tokio::time::timeout(std::time::Duration::from_millis(ms), classify)
+                                .await
+                                .ok();
+                        timed_out = explained.is_none().then_some(ms);
+                        explained
+                    }
+                    (Some(classifier), None) => Some(classifier.classify(classified).await),
+                    (None, _) => None,
+                };
+                let explained = match custom {
+                    Some(explained) => with_continuity(explained, continuity),
+                    None => self.intent_classifier.explain(classified, continuity).await,
+                };
+                Classification::new(explained, self.config.calibration.as_ref())
+            }
+        };
+        let (classified, confidence) = (classification.intent, classification.confidence);
+        let mut routing = RoutingDecision {
+            classified_intent: classified.clone(),
+            final_intent: classified.clone(),
+            reason: classification.reason,
+            confidence,
+            probabilities: classification.probabilities,
+            ..Default::default()
+        };
+        if let Some(ms) = timed_out {
+            routing.note(format!(
+                "classifier timed out after {}ms, fell back to built-in rules",
+                ms
+            ));
+        }
+
+        let intent = match options.forced.clone() {
+            Some(forced) => {
+                routing.forced = true;
+                routing.final_intent = forced.clone();
+                routing.note(format!("forced to {:?}", forced));
+                forced
+            }
+            None => classified,
+        };
+
+        if intent == Intent::Unknown && !self.sub_brains.contains_key(&intent) {
+            routing.final_intent = Intent::QuickAction;
+            routing.note("unknown intent defaults to fast path");
+        }
+
+        // Trade depth for responsiveness while the deep path is slow
+        let max_confidence = self
+            .config
+            .adaptive_max_confidence
+            .unwrap_or(DEFAULT_ADAPTIVE_MAX_CONFIDENCE);
+        if let (Some(threshold), Some(p95)) =
+            (self.config.adaptive_deep_p95_ms, self.deep_latency.p95())
+        {
+            if intent == Intent::Strategy
+                && !routing.forced
+                && !self.sub_brains.contains_key(&intent)
+                && confidence < max_confidence
+                && p95 > threshold
+            {
+                routing.downgraded = true;
+                routing.final_intent = Intent::QuickAction;
+                routing.note(format!(
+                    "adaptive downgrade: deep p95 {}ms over {}ms, confidence {}",
+                    p95, threshold, confidence
+                ));
+            }
+        }
+
+        (intent, routing)
+    }
+
+    /// Format to ask of `intent`'s path: the request's, else the intent's default.
+    fn format_for(&self, intent: &Intent, options: &ProcessOptions) -> Option<Format> {
+        options
+            .format
+            .or_else(|| self.config.intent_default_format.get(intent).copied())
+    }
+
+    /// `input` with its punctuation restored, when it's a voice transcript
+    /// and `voice_preprocessing` is `mode`.
+    fn restored_transcript(
+        &self,
+        input: &str,
+        options: &ProcessOptions,
+        mode: VoicePreprocessing,
+    ) -> Option<String> {
+        (options.source == InputSource::Voice && self.config.voice_preprocessing == Some(mode))
+            .then(|| voice::restore(input))
+    }
+
+    /// Assembles the prompt for `intent`'s model within the token budget,
+    /// rendered through that model's template if it has one.
+    async fn build_prompt(
+        &self,
+        input: &str,
+        intent: &Intent,
+        grounding: &[ScoredChunk],
+        options: &ProcessOptions,
+        seed: u64,
+    ) -> Result<(String, AssembledPrompt), String> {
+        let cite = self.config.citations.is_some() && !grounding.is_empty();
+        let knowledge_texts: Vec<String> = grounding
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                if cite {
+                    citations::label(i + 1, &c.chunk.text)
+                } else {
+                    c.chunk.text.clone()
+                }
+            })
+            .collect();
+
+        let mut system_prompt = self.system_prompt.read().await.clone();
+        if cite {
+            push_section(&mut system_prompt, citations::INSTRUCTION);
+        }
+        if let Some(format) = self.format_for(intent, options) {
+            push_section(&mut system_prompt, format.instruction());
+        }
+        if let Some(answer) = &options.previous_answer {
+            push_section(
+                &mut system_prompt,
+                &format!("{}\n{}", REGENERATE_INSTRUCTION, answer),
+            );
+        }
+        if *intent != Intent::Strategy && !self.tools.is_empty() {
+            push_section(&mut system_prompt, &tools::instruction(&self.tools));
+        }
+        let mut prompt = prompt::assemble(
+            &PromptParts {
+                system: &system_prompt,
+                knowledge: &knowledge_texts,
+                history: &[],
+                input,
+            },
+            self.config.max_prompt_tokens,
+            self.config.overflow_strategy,
+        )?;
+
+        let model = self.model_for(intent, seed);
+        if let Some(template) = self.prompt_templates.read().await.get(&model) {
+            prompt.apply_template(template);
+        }
+        // The template's own text counts against the budget too
+        if let Some(max) = self.config.max_prompt_tokens {
+            if prompt.tokens > max {
+                return Err(format!(
+                    "templated prompt of ~{} tokens exceeds max_prompt_tokens ({})",
+                    prompt.tokens, max
+                ));
+            }
+        }
+
+        Ok((model, prompt))
+    }
+
+    /// Charges a request's tokens to its conversation's budget and returns
+    /// what's left, when a budget applies.
+    fn charge_budget(&self, budget: Option<(u64, &str)>, usage: Option<&Usage>) -> Option<u64> {
+        let (limit, conversation) = budget?;
+        let tokens = usage.map_or(0, |u| u.prompt_tokens + u.completion_tokens);
+        Some(limit.saturating_sub(self.token_ledger.charge(conversation, tokens)))
+    }
+
+    /// Model serving `intent`'s path; fast requests draw from
+    /// `fast_model_pool` when one is configured.
+    fn model_for(&self, intent: &Intent, seed: u64) -> String {
+        if *intent == Intent::Strategy {
+            return self.config.slow_model.clone();
+        }
+
+        // Sorted so the same seed always draws the same model
+        let mut pool: Vec<(&String, u32)> = self
+            .config
+            .fast_model_pool
+            .iter()
+            .map(|(model, weight)| (model, *weight))
+            .filter(|(_, w)| *w > 0)
+            .collect();
+        pool.sort();
+        let total: u32 = pool.iter().map(|(_, w)| w).sum();
+        if total == 0 {
+            return self.config.fast_model.clone();
+        }
+
+        let mut ticket = rand::rngs::StdRng::seed_from_u64(seed).gen_range(0..total);
+        for (model, weight) in pool {
+            if ticket < weight {
+                return model.clone();
+            }
+            ticket -= weight;
+        }
+        unreachable!("ticket is below the total weight")
+    }
+
+    /// Knowledge chunks to inject when grounding an `intent` answer to
+    /// `input`, plus how many were retrieved before filtering. Strategy
+    /// answers are always grounded; others only when some document is
+    /// scoped to their intent.
+    async fn grounding(
+        &self,
+        input: &str,
+        intent: &Intent,
+        options: &ProcessOptions,
+    ) -> (Vec<ScoredChunk>, usize) {
+        let knowledge = self.knowledge.current().await;
+        if *intent != Intent::Strategy && !knowledge.scopes(intent) {
+            return (Vec::new(), 0);
+        }
+        let top_k = self
+            .config
+            .knowledge_top_k
+            .unwrap_or(DEFAULT_KNOWLEDGE_TOP_K);
+        let candidates = if self.config.rerank {
+            self.config
+                .rerank_candidates
+                .unwrap_or(DEFAULT_RERANK_CANDIDATES)
+                .max(top_k)
+        } else {
+            top_k
+        };
+        let mut chunks = knowledge.retrieve(input, intent, options.persona.as_deref(), candidates);
+        let retrieved = chunks.len();
+
+        if let Some(min_score) = self.config.min_chunk_score {
+            chunks.retain(|c| c.score >= min_score);
+        }
+        if self.config.rerank {
+            for chunk in &mut chunks {
+                chunk.rerank_score = Some(self.cross_encode(input, &chunk.chunk.text).await);
+            }
+            chunks.sort_by(|a, b| {
+                b.rerank_score
+                    .unwrap_or(0.0)
+                    .total_cmp(&a.rerank_score.unwrap_or(0.0))
+            });
+            chunks.truncate(top_k);
+        }
+        if let Some(max) = self.config.max_injected_chunks {
+            chunks.truncate(max);
+        }
+        (chunks, retrieved)
+    }
+
+    /// Relevance (0-1) of `passage` to `query` from the `rerank_model`
+    /// cross-encoder.
+    async fn cross_encode(&self, query: &str, passage: &str) -> f32 {
+        // For now, mock cross-encoder: share of the passage's words that
+        // appear in the query, favouring focused passages
+        let query = query.to_lowercase();
+        let words: Vec<String> = passage
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| w.len() > 2)
+            .map(str::to_lowercase)
+            .collect();
+        if words.is_empty() {
+            return 0.0;
+        }
+        words.iter().filter(|w| query.contains(w.as_str())).count() as f32 / words.len() as f32
+    }
+
+    async fn forward(
+        &self,
+        replicas: &[SubBrain],
+        input: &str,
+        seed: u64,
+        request_id: String,
+        mut routing: RoutingDecision,
+        start: std::time::Instant,
+    ) -> Result<Response, ProcessError> {
+        let intent = routing.final_intent.clone();
+
+        // Try each region in turn; the last one gets as long as it needs
+        let mut failures = Vec::new();
+        let mut answered = None;
+        for (i, replica) in replicas.iter().enumerate() {
+            let call = replica.client.process(input, Some(seed));
+            let result = match self
+                .config
+                .region_failover_ms
+                .filter(|_| i + 1 < replicas.len())
+            {
+                Some(ms) => tokio::time::timeout(std::time::Duration::from_millis(ms), call)
+                    .await
+                    .unwrap_or_else(|_| Err(format!("no answer within {}ms", ms))),
+                None => call.await,
+            };
+            match result {
+                Ok(remote) => {
+                    answered = Some((replica, remote));
+                    break;
+                }
+                Err(e) => failures.push(format!(
+                    "{} ({})",
+                    replica
+                        .region
+                        .as_deref()
+                        .unwrap_or(replica.client.base_url()),
+                    e
+                )),
+            }
+        }
+        let Some((sub_brain, mut remote)) = answered else {
+            let backends: Vec<&str> = replicas.iter().map(|r| r.client.base_url()).collect();
+            let message = format!("every sub-brain region failed: {}", failures.join("; "));
+            return Err(ProcessError::new(
+                Phase::Generation,
+                ErrorCategory::BackendUnavailable,
+                message,
+            )
+            .with_backend(backends.join(", ")));
+        };
+        if let Some(region) = &sub_brain.region {
+            remote.system = format!("{}@{}", remote.system, region);
+        }
+        if !failures.is_empty() {
+            routing.note(format!("failed over from {}", failures.join(", ")));
+        }
+        let sub_brain = &sub_brain.client;
+
+        let note = format!(
+            "Routed {:?} to sub-brain at {} (remote intent {:?})",
+            intent,
+            sub_brain.base_url(),
+            remote.intent
+        );
+        let reasoning_trace = Some(match remote.reasoning_trace {
+            Some(trace) => format!("{}\n{}", note, trace),
+            None => note,
+        });
+
+        routing.note(format!(
+            "forwarded to sub-brain at {}",
+            sub_brain.base_url()
+        ));
+        routing.final_intent = remote.intent;
+
+        Ok(self.seal(
+            Response {
+                intent,
+                token_budget_remaining: None,
+                reasoning_trace,
+                internal_trace: None,
+                latency_ms: start.elapsed().as_millis() as u64,
+                routing,
+                request_id,
+                input_hash: signing::input_hash(input),
+                signature: None,
+                ..remote
+            },
+            &[sub_brain.base_url().to_string()],
+        ))
+    }
+
+    /// Enforces `max_response_bytes`, applies `redact_trace` (masking
+    /// `sensitive` fragments), `trace_by_intent` and `max_trace_chars`, and
+    /// signs the response when a signing key is configured.
+    fn seal(&self, response: Response, sensitive: &[String]) -> Response {
+        self.seal_with(response, sensitive, None)
+    }
+
+    /// `seal` for content this brain generated, which also carries the
+    /// configured watermark.
+    fn seal_generated(&self, response: Response, sensitive: &[String]) -> Response {
+        self.seal_with(response, sensitive, self.config.watermark)
+    }
+
+    fn seal_with(
+        &self,
+        mut response: Response,
+        sensitive: &[String],
+        watermark: Option<WatermarkStyle>,
+    ) -> Response {
+        let mark = self.watermark_for(&response.request_id, watermark);
+        self.fit(&mut response, mark.as_ref().map_or(0, String::len));
+
+        if self.config.redact_trace {
+            if let Some(trace) = &response.reasoning_trace {
+                let redacted = redact(trace, sensitive);
+                if &redacted != trace {
+                    response.internal_trace = response.reasoning_trace.replace(redacted);
+                }
+            }
+            response.routing.reason = redact(&response.routing.reason, sensitive);
+        }
+
+        if self
+            .config
+            .trace_by_intent
+            .get(&response.routing.final_intent)
+            == Some(&false)
+        {
+            if let Some(trace) = response.reasoning_trace.take() {
+                response.internal_trace.get_or_insert(trace);
+            }
+        }
+
+        if let Some(max) = self.config.max_trace_chars {
+            for trace in [&mut response.reasoning_trace, &mut response.internal_trace]
+                .into_iter()
+                .flatten()
+            {
+                cap_trace(trace, max);
+            }
+        }
+
+        self.stamp(&mut response, mark);
+        response
+    }
+
+    /// A coalesced follower's copy of the leader's sealed `response`, under
+    /// its own `request_id`: re-watermarked if the leader's was, and
+    /// re-signed.
+    fn reissue(&self, mut response: Response, request_id: String) -> Response {
+        let leader_mark = self
+            .config
+            .watermark
+            .map(|style| (style, watermark::apply("", &response.request_id, style)))
+            .filter(|(_, mark)| response.content.ends_with(mark.as_str()));
+        if let Some((_, mark)) = &leader_mark {
+            response
+                .content
+                .truncate(response.content.len() - mark.len());
+        }
+        response.request_id = request_id;
+        response.signature = None;
+
+        let mark = self.watermark_for(&response.request_id, leader_mark.map(|(style, _)| style));
+        self.fit(&mut response, mark.as_ref().map_or(0, String::len));
+        self.stamp(&mut response, mark);
+        response
+    }
+
+    /// The watermark to append for `request_id` in `style`, unless the mark
+    /// alone wouldn't fit `max_response_bytes`.
+    fn watermark_for(&self, request_id: &str, style: Option<WatermarkStyle>) -> Option<String> {
+        let max = self.config.max_response_bytes;
+        style
+            .map(|style| watermark::apply("", request_id, style))
+            .filter(|mark| max.is_none_or(|max| mark.len() <= max))
+    }
+
+    /// Cuts content to `max_response_bytes`, less `reserved` bytes kept for
+    /// the watermark, since marking happens after truncation.
+    fn fit(&self, response: &mut Response, reserved: usize) {
+        let Some(max) = self.config.max_response_bytes else {
+            return;
+        };
+        if response.content.len() + reserved > max {
+            // The marker counts toward the cap, and is itself cut to fit it
+            let max = max - reserved;
+            let marker = self
+                .config
+                .truncation_marker
+                .as_deref()
+                .unwrap_or(DEFAULT_TRUNCATION_MARKER);
+            let marker = &marker[..char_floor(marker, max)];
+            let end = char_floor(&response.content, max - marker.len());
+            response.content.truncate(end);
+            response.content.push_str(marker);
+            response.truncated = true;
+            push_trace(
+                &mut response.reasoning_trace,
+                format!("Content truncated to {} bytes (max_response_bytes)", end),
+            );
+        }
+    }
+
+    /// Appends the watermark `mark` and signs the response when a signing
+    /// key is configured.
+    fn stamp(&self, response: &mut Response, mark: Option<String>) {
+        if let Some(mark) = mark {
+            response.content.push_str(&mark);
+        }
+        if let Some(key) = &self.config.signing_key {
+            response.signature = Some(signing::sign(
+                key,
+                &response.request_id,
+                &response.input_hash,
+                &response.content,
+            ));
+        }
+    }
+
+    /// Detected language of `input` when translation is enabled and the
+    /// input is reliably something other than English.
+    fn foreign_language(&self, input: &str) -> Option<String> {
+        self.config.translation_model.as_ref()?;
+        let info = whatlang::detect(input)?;
+        (info.is_reliable() && info.lang() != whatlang::Lang::Eng)
+            .then(|| info.lang().code().to_string())
+    }
+
+    async fn translate(&self, text: &str, from: &str, to: &str) -> String {
+        // Translation via `translation_model`
+        // For now, return mock translation
+        format!("[{}->{}] {}", from, to, text)
+    }
+
+    /// Answers `prompt` on the routed path: the deep model within its soft
+    /// timeout, or the fast model through its tool loop and self-check.
+    async fn generate(
+        &self,
+        prompt: &AssembledPrompt,
+        context: Option<&Value>,
+        grounding: &[ScoredChunk],
+        seed: u64,
+        routing: &mut RoutingDecision,
+        retries: &mut RetryBudget,
+    ) -> (String, String, Option<String>) {
+        match routing.final_intent {
+            Intent::Strategy => {
+                // System 2: Deep reasoning with grounding
+                self.deep_reason_within_soft_timeout(
+                    &prompt.text,
+                    context,
+                    grounding,
+                    seed,
+                    routing,
+                )
+                .await
+            }
+            Intent::QuickAction | Intent::Unknown => {
+                // System 1: Fast, local model (and the safe default)
+                if self.tools.is_empty() {
+                    self.fast_execute_checked(&prompt.text, context, seed, retries)
+                        .await
+                } else {
+                    self.fast_execute_with_tools(prompt, context, seed, retries)
+                        .await
+                }
+            }
+        }
+    }
+
+    /// Fast path with the optional `fast_self_check` critique pass.
+    async fn fast_execute_checked(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        seed: u64,
+        retries: &mut RetryBudget,
+    ) -> (String, String, Option<String>) {
+        let (system, content, trace) = self.fast_execute(input, context, seed).await;
+        if !self.config.fast_self_check {
+            return (system, content, trace);
+        }
+
+        let (system, content, trace, outcome) = match self.fast_critique(input, &content).await {
+            None => (system, content, trace, "Self-check: passed".to_string()),
+            Some(issue) if !retries.take() => (
+                system,
+                content,
+                trace,
+                format!(
+                    "Self-check flagged \"{}\"; retry budget exhausted, kept answer",
+                    issue
+                ),
+            ),
+            Some(issue) => {
+                let revised = format!("{}\n\n{} {}", input, SELF_CHECK_INSTRUCTION, issue);
+                let (system, content, trace) = self
+                    .fast_execute(&revised, context, seed.wrapping_add(1))
+                    .await;
+                (
+                    system,
+                    content,
+                    trace,
+                    format!("Self-check flagged \"{}\"; regenerated once", issue),
+                )
+            }
+        };
+
+        let trace = Some(match trace {
+            Some(t) => format!("{}\n{}", t, outcome),
+            None => outcome,
+        });
+        (system, content, trace)
+    }
+
+    /// Fast path as a bounded agent loop: while the model asks for a tool
+    /// with at least `min_tool_confidence`, run it and feed the result back,
+    /// up to `max_tool_iterations` calls, then answer from the accumulated
+    /// results.
+    async fn fast_execute_with_tools(
+        &self,
+        prompt: &AssembledPrompt,
+        context: Option<&Value>,
+        seed: u64,
+        retries: &mut RetryBudget,
+    ) -> (String, String, Option<String>) {
+        let max = self
+            .config
+            .max_tool_iterations
+            .unwrap_or(DEFAULT_MAX_TOOL_ITERATIONS);
+        let mut transcript = prompt.text.clone();
+        let mut calls: Vec<ToolCall> = Vec::new();
+        let mut trace = Vec::new();
+
+        loop {
+            let step = self.fast_tool_step(&prompt.user, &calls).await;
+            let Some(call) = tools::parse_call(&step) else {
+                break;
+            };
+            if calls.len() == max {
+                trace.push(format!(
+                    "Tool loop stopped after {} call(s); answering with results so far",
+                    max
+                ));
+                break;
+            }
+            if let Some(min) = self.config.min_tool_confidence {
+                let confidence = call
+                    .confidence
+                    .unwrap_or_else(|| hedging::answer_confidence(&step, None));
+                if confidence < min {
+                    trace.push(format!(
+                        "Tool call {}({}) suppressed: confidence {:.2} below min_tool_confidence {}",
+                        call.name, call.input, confidence, min
+                    ));
+                    break;
+                }
+            }
+            let result = match self.tools.iter().find(|t| t.name() == call.name) {
+                Some(tool) => tool
+                    .call(&call.input)
+                    .await
+                    .unwrap_or_else(|e| format!("error: {}", e)),
+                None => format!("error: no tool named '{}'", call.name),
+            };
+            trace.push(format!(
+                "Tool call {}: {}({}) -> {}",
+                calls.len() + 1,
+                call.name,
+                call.input,
+                result
+            ));
+            transcript.push_str(&format!("\n\nTool {} returned: {}", call.name, result));
+            calls.push(call);
+        }
+
+        let (system, content, answer_trace) = self
+            .fast_execute_checked(&transcript, context, seed, retries)
+            .await;
+        trace.extend(answer_trace);
+        (
+            system,
+            content,
+            (!trace.is_empty()).then(|| trace.join("\n")),
+        )
+    }
+
+    /// One fast-model step of the tool loop, given the calls made so far:
+    /// either a tool call or an answer.
+    async fn fast_tool_step(&self, input: &str, calls: &[ToolCall]) -> String {
+        // For now, mock tool use: call each registered tool the user turn
+        // names, once
+        let lowered = input.to_lowercase();
+        self.tools
+            .iter()
+            .find(|t| {
+                lowered.contains(&t.name().to_lowercase())
+                    && !calls.iter().any(|c| c.name == t.name())
+            })
+            .map(|t| format!("{}{}: {}", tools::CALL_PREFIX, t.name(), input))
+            .unwrap_or_default()
+    }
+
+    /// Asks the fast model "is this answer correct and complete?",
+    /// returning the issue it raises, if any.
+    async fn fast_critique(&self, _input: &str, answer: &str) -> Option<String> {
+        // For now, mock critique: only an empty answer or a leftover TODO
+        // is flagged
+        if answer.trim().is_empty() {
+            Some("answer is empty".to_string())
+        } else if answer.contains("TODO") {
+            Some("answer contains a TODO placeholder".to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Classifies and answers each chunk of an over-length directive on the
+    /// fast model, returning the partial answers as the synthesis input.
+    async fn map_chunks(&self, chunks: &[&str], context: Option<&Value>, seed: u64) -> String {
+        let mut partials = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.iter().enumerate() {
+            let (intent, _, _) = self.intent_classifier.explain(chunk, None).await;
+            let (_, answer, _) = self.fast_execute(chunk, context, seed).await;
+            partials.push(format!(
+                "Part {}/{} ({:?}): {}",
+                i + 1,
+                chunks.len(),
+                intent,
+                answer
+            ));
+        }
+        format!(
+            "Synthesize one answer from these partial answers:\n\n{}",
+            partials.join("\n\n")
+        )
+    }
+
+    /// Asks the fast model to rewrite `answer` following the style `guide`.
+    async fn restyle(&self, answer: &str, _guide: &str) -> String {
+        // For now, return mock rewrite
+        format!("[styled] {}", answer)
+    }
+
+    /// Asks the fast model for a brief TL;DR of `answer`.
+    async fn fast_summarize(&self, answer: &str) -> String {
+        // For now, mock summary: the answer's first sentence, capped in length
+        let first = stream::sentences(answer)
+            .first()
+            .copied()
+            .unwrap_or_default();
+        let words: Vec<&str> = first.split_whitespace().collect();
+        if words.len() > SUMMARY_MAX_WORDS {
+            format!("{}…", words[..SUMMARY_MAX_WORDS].join(" "))
+        } else {
+            words.join(" ")
+        }
+    }
+
+    async fn fast_execute(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        _seed: u64,
+    ) -> (String, String, Option<String>) {
+        // System 1: < 200ms, local Llama via Ollama
+        // For now, return mock response
+        let content = match context {
+            Some(ctx) => format!("[FAST] Processed: {} (context: {})", input, ctx),
+            None => format!("[FAST] Processed: {}", input),
+        };
+
+        ("cortex".to_string(), content, None)
+    }
+
+    /// Runs the deep path, falling back to the fast model if it hasn't
+    /// finished within `soft_timeout_ms`.
+    async fn deep_reason_within_soft_timeout(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        grounding: &[ScoredChunk],
+        seed: u64,
+        routing: &mut RoutingDecision,
+    ) -> (String, String, Option<String>) {
+        let deep = self.deep_reason(input, context, grounding, seed);
+        self.within_soft_timeout(deep, input, context, seed, routing)
+            .await
+    }
+
+    /// Awaits the `deep` answer, or answers `input` on the fast model once
+    /// `deep` has run past `soft_timeout_ms`.
+    async fn within_soft_timeout(
+        &self,
+        deep: impl std::future::Future<Output = (String, String, Option<String>)>,
+        input: &str,
+        context: Option<&Value>,
+        seed: u64,
+        routing: &mut RoutingDecision,
+    ) -> (String, String, Option<String>) {
+        let start = std::time::Instant::now();
+        let Some(ms) = self.config.soft_timeout_ms else {
+            let result = deep.await;
+            self.deep_latency.record(start.elapsed().as_millis() as u64);
+            return result;
+        };
+
+        match tokio::time::timeout(std::time::Duration::from_millis(ms), deep).await {
+            Ok(result) => {
+                self.deep_latency.record(start.elapsed().as_millis() as u64);
+                result
+            }
+            Err(_) => {
+                // The deep model took at least this long
+                self.deep_latency.record(ms);
+                routing.downgraded = true;
+                routing.final_intent = Intent::QuickAction;
+                routing.note(format!("deep path exceeded {}ms soft timeout", ms));
+                let (system, content, trace) = self.fast_execute(input, context, seed).await;
+                let note = format!(
+                    "Degraded: deep model exceeded soft timeout of {}ms, answered with fast model",
+                    ms
+                );
+                let trace = Some(match trace {
+                    Some(t) => format!("{}\n{}", note, t),
+                    None => note,
+                });
+                (system, content, trace)
+            }
+        }
+    }
+
+    async fn deep_reason(
+        &self,
+        input: &str,
+        context: Option<&Value>,
+        grounding: &[ScoredChunk],
+        _seed: u64,
+    ) -> (String, String, Option<String>) {
+        // System 2: Full reasoning with grounding pass
+        let mut reasoning = format!("[DEEP] Analyzing strategy for: {}", input);
+        if self.config.citations.is_some() {
+            // For now, mock citations: every injected chunk is cited
+            for marker in 1..=grounding.len() {
+                reasoning.push_str(&format!(" [{}]", marker));
+            }
+        }
+
+        // Grounding pass would happen here
+        let sources: Vec<&str> = grounding.iter().map(|c| c.chunk.id.as_str()).collect();
+        let mut trace = format!(
+            "1. Loaded {} knowledge chunk(s) from {:?} [{}]\n2. Grounding against constitution\n3. Generated plan",
+            grounding.len(),
+            self.config.knowledge_path,
+            sources.join(", ")
+        );
+        if let Some(ctx) = context {
+            trace.push_str(&format!("\n4. Considered structured context: {}", ctx));
+        }
+
+        // Each phase is bounded on its own, so neither can starve the other
+        let (reasoning_budget, answer_budget) = (
+            self.config.reasoning_token_budget,
+            self.config.answer_token_budget,
+        );
+        if reasoning_budget.is_some() || answer_budget.is_some() {
+            trace = within_budget(&trace, reasoning_budget).to_string();
+            reasoning = within_budget(&reasoning, answer_budget).to_string();
+            let used = |text: &str, budget: Option<usize>| match budget {
+                Some(budget) => format!("{}/{}", prompt::estimate_tokens(text), budget),
+                None => prompt::estimate_tokens(text).to_string(),
+            };
+            let report = format!(
+                "\nTokens used: reasoning {}, answer {}",
+                used(&trace, reasoning_budget),
+                used(&reasoning, answer_budget)
+            );
+            trace.push_str(&report);
+        }
+        let trace = Some(trace);
+
+        ("deep_mind".to_string(), reasoning, trace)
+    }
+}
+
+/// The words of `text` that fit within `budget` tokens.
+fn within_budget(text: &str, budget: Option<usize>) -> &str {
+    match budget {
+        Some(budget) => prompt::chunk(text, budget)
+            .first()
+            .map_or("", |c| c.trim_end()),
+        None => text,
+    }
+}
+
+fn push_section(text: &mut String, section: &str) {
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+    text.push_str(section);
+}
+
+/// Retries left for one request, shared by every retry point.
+struct RetryBudget(Option<u32>);
+
+impl RetryBudget {
+    /// Spends a retry, returning false once the budget is exhausted.
+    fn take(&mut self) -> bool {
+        match &mut self.0 {
+            None => true,
+            Some(0) => false,
+            Some(left) => {
+                *left -= 1;
+                true
+            }
+        }
+    }
+}
+
+/// Classifier confidence below which a fast answer gets a deep-path hint.
+const MARGINAL_CONFIDENCE: f32 = 0.7;
+
+/// Routing hints for the client, without spending more on its behalf.
+fn suggestions(routing: &RoutingDecision) -> Vec<String> {
+    let mut suggestions = Vec::new();
+    if routing.forced {
+        return suggestions;
+    }
+
+    if routing.downgraded {
+        suggestions.push(
+            "The deep path was skipped to stay responsive; retry later or use mode=two_phase for a deep answer".to_string(),
+        );
+    } else if routing.escalation_suggested {
+        suggestions.push(
+            "This answer looks like it needed deeper analysis; use mode=two_phase for a deep answer".to_string(),
+        );
+    } else if routing.final_intent == Intent::QuickAction
+        && routing.confidence < MARGINAL_CONFIDENCE
+    {
+        suggestions.push(
+            "This looked like a quick question but might benefit from deep analysis; use mode=two_phase for a deep answer"
+                .to_string(),
+        );
+    }
+    suggestions
+}
+
+fn push_trace(trace: &mut Option<String>, note: String) {
+    *trace = Some(match trace.take() {
+        Some(existing) => format!("{}\n{}", existing, note),
+        None => note,
+    });
+}
+
+/// Largest char boundary in `text` at or below byte `at`.
+fn char_floor(text: &str, at: usize) -> usize {
+    (0..=at.min(text.len()))
+        .rev()
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(0)
+}
+
+/// Cuts `trace` to its first `max` characters and notes how much was left
+/// out.
+fn cap_trace(trace: &mut String, max: usize) {
+    let total = trace.chars().count();
+    if let Some((end, _)) = trace.char_indices().nth(max) {
+        trace.truncate(end);
+        trace.push_str(&format!(
+            "\n…[trace truncated: {} of {} chars shown]",
+            max, total
+        ));
+    }
+}
+
+/// Replaces each non-empty `sensitive` fragment in `text` with `[redacted]`,
+/// longest first so overlapping fragments are fully masked.
+fn redact(text: &str, sensitive: &[String]) -> String {
+    let mut fragments: Vec<&String> = sensitive.iter().filter(|s| !s.is_empty()).collect();
+    fragments.sort_by_key(|s| std::cmp::Reverse(s.len()));
+
+    fragments
+        .into_iter()
+        .fold(text.to_string(), |text, fragment| {
+            text.replace(fragment.as_str(), "[redacted]")
+        })
+}
+
+const STRATEGY_KEYWORDS: &[&str] = &[
+    "plan",
+    "strategy",
+    "analyze",
+    "build architecture",
+    "design",
+    "roadmap",
+    "approach",
+];
+
+const QUICK_KEYWORDS: &[&str] = &["what", "how"];
+
+/// Added to the system prompt, followed by the rejected answer, when
+/// regenerating.
+const REGENERATE_INSTRUCTION: &str =
+    "The user asked for a new answer. Write a fresh one that differs from this earlier answer:";
+
+/// Appended to the directive when regenerating a repeated answer.
+const NEW_PERSPECTIVE_INSTRUCTION: &str =
+    "You already gave this answer earlier in the conversation. Offer a different or additional perspective instead of repeating it.";
+
+/// Appended to the directive, followed by the critique, when the fast
+/// self-check regenerates an answer.
+const SELF_CHECK_INSTRUCTION: &str =
+    "Your previous answer to this was flagged in review. Answer again and fix this issue:";
+
+/// Share of distinct words two answers have in common (Jaccard), 0-1.
+fn word_overlap(a: &str, b: &str) -> f32 {
+    let words = |text: &str| -> std::collections::HashSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// The strategy keyword found in `answer`, if it is at least `min_len`
+/// characters long.
+fn strategy_marker(answer: &str, min_len: usize) -> Option<&'static str> {
+    if answer.chars().count() < min_len {
+        return None;
+    }
+    let answer = answer.to_lowercase();
+    STRATEGY_KEYWORDS
+        .iter()
+        .find(|k| answer.contains(*k))
+        .copied()
+}
+
+const IMPERATIVE_VERBS: &[&str] = &[
+    "add",
+    "convert",
+    "fix",
+    "give",
+    "list",
+    "rename",
+    "run",
+    "show",
+    "summarize",
+    "tell",
+    "translate",
+    "write",
+];
+
+struct IntentClassifier {
+    // Lightweight classifier for fast intent detection
+    structure: Option<StructuralWeights>,
+    entities: Option<EntityRules>,
+    complexity_threshold: Option<f32>,
+}
+
+impl IntentClassifier {
+    fn new(
+        structure: Option<StructuralWeights>,
+        entities: Option<EntityRules>,
+        complexity_threshold: Option<f32>,
+    ) -> Self {
+        Self {
+            structure,
+            entities,
+            complexity_threshold,
+        }
+    }
+
+    #[cfg(test)]
+    async fn classify(&self, input: &str) -> Intent {
+        self.explain(input, None).await.0
+    }
+
+    /// Classifies `input`, describing which rule decided it and how
+    /// confident that rule is: a single keyword match scores 0.7, several
+    /// 0.9.
+    ///
+    /// With `continuity` (the previous turn's intent and the bias weight),
+    /// the previous intent wins whenever the weight beats the confidence of
+    /// the matching rule.
+    async fn explain(
+        &self,
+        input: &str,
+        continuity: Option<(&Intent, f32)>,
+    ) -> (Intent, String, f32) {
+        // Simple keyword-based classification
+        // In production: use a tiny local model
+        let (intent, reason, confidence) = keyword_rule(input);
+
+        let (intent, reason, confidence) =
+            match self.structure.as_ref().map(|w| structural_signal(input, w)) {
+                Some((score, cues)) if score.abs() > confidence => {
+                    let structural = if score > 0.0 {
+                        Intent::Strategy
+                    } else {
+                        Intent::QuickAction
+                    };
+                    if structural == intent {
+                        (intent, reason, confidence)
+                    } else {
+                        let reason = format!(
+                            "{} overridden: structure ({}) scores {:+.1}",
+                            reason,
+                            cues.join(", "),
+                            score
+                        );
+                        (structural, reason, score.abs().min(1.0))
+                    }
+                }
+                _ => (intent, reason, confidence),
+            };
+
+        let (intent, reason, confidence) =
+            match self.entities.as_ref().map(|rules| rules.score(input)) {
+                Some((score, kinds))
+                    if score.abs() > confidence && EntityRules::intent(score) != intent =>
+                {
+                    let kinds: Vec<String> = kinds
+                        .iter()
+                        .map(|k| format!("{:?}", k).to_lowercase())
+                        .collect();
+                    let reason = format!(
+                        "{} overridden: entities ({}) score {:+.1}",
+                        reason,
+                        kinds.join(", "),
+                        score
+                    );
+                    (EntityRules::intent(score), reason, score.abs().min(1.0))
+                }
+                _ => (intent, reason, confidence),
+            };
+
+        let (intent, reason, confidence) =
+            match self.complexity_threshold.map(|t| (t, complexity(input))) {
+                Some((threshold, score)) if score >= threshold && intent != Intent::Strategy => (
+                    Intent::Strategy,
+                    format!(
+                        "{} overridden: complexity {:.2} >= {}",
+                        reason, score, threshold
+                    ),
+                    score,
+                ),
+                _ => (intent, reason, confidence),
+            };
+
+        with_continuity((intent, reason, confidence), continuity)
+    }
+}
+
+/// Keeps the previous turn's intent when the `continuity` weight beats the
+/// confidence of the classification `explained`.
+fn with_continuity(
+    explained: (Intent, String, f32),
+    continuity: Option<(&Intent, f32)>,
+) -> (Intent, String, f32) {
+    let (intent, reason, confidence) = explained;
+    match continuity {
+        Some((previous, weight)) if *previous != intent && weight > confidence => (
+            previous.clone(),
+            format!(
+                "{} overridden: follow-up to a {:?} turn (continuity {} > confidence {})",
+                reason, previous, weight, confidence
+            ),
+            weight,
+        ),
+        _ => (intent, reason, confidence),
+    }
+}
+
+/// The keyword rules on their own: the built-in classifier's first step.
+fn keyword_rule(input: &str) -> (Intent, String, f32) {
+    let input_lower = input.to_lowercase();
+    let keyword_confidence = |keywords: &[&str]| {
+        if keywords.iter().filter(|k| input_lower.contains(*k)).count() > 1 {
+            0.9
+        } else {
+            0.7
+        }
+    };
+
+    if let Some(keyword) = STRATEGY_KEYWORDS.iter().find(|k| input_lower.contains(*k)) {
+        (
+            Intent::Strategy,
+            format!("keyword match '{}'", keyword),
+            keyword_confidence(STRATEGY_KEYWORDS),
+        )
+    } else if let Some(keyword) = QUICK_KEYWORDS.iter().find(|k| input_lower.contains(*k)) {
+        (
+            Intent::QuickAction,
+            format!("keyword match '{}'", keyword),
+            keyword_confidence(QUICK_KEYWORDS),
+        )
+    } else if input_lower.len() < 50 {
+        (Intent::QuickAction, "short input".to_string(), 0.5)
+    } else {
+        (Intent::Unknown, "no rule matched".to_string(), 0.2)
+    }
+}
+
+/// Combined weight of the structural cues present in `input`, with the
+/// names of those cues.
+fn structural_signal(input: &str, weights: &StructuralWeights) -> (f32, Vec<&'static str>) {
+    let trimmed = input.trim();
+    let numbered_lines = trimmed
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            let digits = line.chars().take_while(char::is_ascii_digit).count();
+            digits > 0 && matches!(line[digits..].chars().next(), Some('.') | Some(')'))
+        })
+        .count();
+    let first_word = trimmed
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let cues = [
+        ("question", trimmed.ends_with('?'), weights.question),
+        ("numbered list", numbered_lines >= 2, weights.numbered_list),
+        ("code fence", trimmed.contains("```"), weights.code_fence),
+        (
+            "imperative",
+            IMPERATIVE_VERBS.contains(&first_word.as_str()),
+            weights.imperative,
+        ),
+    ];
+
+    cues.iter().filter(|(_, present, _)| *present).fold(
+        (0.0, Vec::new()),
+        |(score, mut names), (name, _, weight)| {
+            names.push(*name);
+            (score + weight, names)
+        },
+    )
+}
+
+/// Words, clauses, extra questions and sub-tasks at which an input counts
+/// as fully complex on that measure.
+const COMPLEXITY_WORDS: f32 = 80.0;
+const COMPLEXITY_CLAUSES: f32 = 6.0;
+const COMPLEXITY_QUESTIONS: f32 = 3.0;
+const COMPLEXITY_SUBTASKS: f32 = 4.0;
+const CLAUSE_MARKERS: &[&str] = &[
+    ",",
+    ";",
+    " and ",
+    " but ",
+    " because ",
+    " while ",
+    " which ",
+    " unless ",
+    " if ",
+];
+const SUBTASK_MARKERS: &[&str] = &["first", "then", "also", "next", "finally", "after that"];
+
+/// Estimated complexity of `input` from 0 (trivial) to 1, from its length,
+/// clause count, question nesting and number of sub-tasks.
+pub fn complexity(input: &str) -> f32 {
+    let lower = input.to_lowercase();
+    let words = lower.split_whitespace().count() as f32;
+    let clauses = CLAUSE_MARKERS
+        .iter()
+        .map(|m| lower.matches(m).count())
+        .sum::<usize>() as f32;
+    // Several questions in a row, or one tucked inside another
+    let questions =
+        (lower.matches('?').count().saturating_sub(1) + lower.matches('(').count()) as f32;
+    let list_items = lower
+        .lines()
+        .map(str::trim_start)
+        .filter(|line| {
+            line.starts_with("- ")
+                || line.starts_with("* ")
+                || line.starts_with(|c: char| c.is_ascii_digit())
+        })
+        .count();
+    let sequenced = lower
+        .split(['.', ',', ';', '\n'])
+        .map(str::trim_start)
+        .filter(|clause| SUBTASK_MARKERS.iter().any(|m| clause.starts_with(m)))
+        .count();
+    let subtasks = (list_items + sequenced) as f32;
+
+    0.3 * (words / COMPLEXITY_WORDS).min(1.0)
+        + 0.25 * (clauses / COMPLEXITY_CLAUSES).min(1.0)
+        + 0.2 * (questions / COMPLEXITY_QUESTIONS).min(1.0)
+        + 0.25 * (subtasks / COMPLEXITY_SUBTASKS).min(1.0)
+}
+
+pub type SharedBrain = Arc<Brain>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_intent_classification() {
+        let classifier = IntentClassifier::new(None, None, None);
 
-    #[tokio::test]
-    async fn test_intent_classification() {
-        let classifier = IntentClassifier::new();
-        
         let intent = classifier.classify("Build a pricing strategy").await;
         assert_eq!(intent, Intent::Strategy);
         
         let intent = classifier.classify("What time is it?").await;
         assert_eq!(intent, Intent::QuickAction);
     }
+
+    #[tokio::test]
+    async fn test_continuity_bias_keeps_follow_ups_on_strategy() {
+        let classifier = IntentClassifier::new(None, None, None);
+        let previous = Some((&Intent::Strategy, 0.6));
+
+        let (intent, reason, _) = classifier.explain("and the risks?", previous).await;
+        assert_eq!(intent, Intent::Strategy);
+        assert!(reason.starts_with("short input overridden"));
+
+        // A keyword match is confident enough to change topic
+        let (intent, _, _) = classifier.explain("What time is it?", previous).await;
+        assert_eq!(intent, Intent::QuickAction);
+    }
+
+    #[tokio::test]
+    async fn test_structural_cues_override_weak_rules() {
+        let classifier = IntentClassifier::new(Some(StructuralWeights::default()), None, None);
+
+        let steps = "Here is where we are today with the team, for context:\n1. Churn is up\n2. Hiring is frozen";
+        let (intent, reason, _) = classifier.explain(steps, None).await;
+        assert_eq!(intent, Intent::Strategy);
+        assert!(reason.contains("numbered list"));
+
+        // Weak structure doesn't beat a keyword match
+        let (intent, _, _) = classifier.explain("Write the growth plan", None).await;
+        assert_eq!(intent, Intent::Strategy);
+    }
+
+    #[tokio::test]
+    async fn test_compare_classifier() {
+        let brain = Brain::new(BrainConfig::default());
+        let proposed = ClassifierConfig {
+            structural_weights: Some(StructuralWeights::default()),
+            ..Default::default()
+        };
+        let inputs = vec![
+            "Here is where we are today with the team, for context:\n1. Churn is up\n2. Hiring is frozen".to_string(),
+            "Write the growth plan".to_string(),
+        ];
+
+        let diffs = brain.compare_classifier(&proposed, &inputs).await;
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs[0].changed);
+        assert_eq!(diffs[0].current.intent, Intent::Unknown);
+        assert_eq!(diffs[0].proposed.intent, Intent::Strategy);
+        assert!(!diffs[1].changed);
+    }
+
+    #[tokio::test]
+    async fn test_long_deep_answers_get_a_summary() {
+        let brain = Brain::new(BrainConfig {
+            summary_min_chars: Some(40),
+            ..Default::default()
+        });
+        let strategy = ProcessOptions {
+            forced: Some(Intent::Strategy),
+            ..Default::default()
+        };
+
+        let response = brain
+            .process("Plan our expansion into Europe", None, &strategy)
+            .await
+            .unwrap();
+        let summary = response.summary.unwrap();
+        assert!(!summary.is_empty());
+        assert!(response.content.starts_with(&summary));
+
+        // Short answers and fast answers don't get one
+        let response = brain.process("Plan", None, &strategy).await.unwrap();
+        assert_eq!(response.summary, None);
+        let response = brain
+            .process(
+                "What time is it in Paris right now, please?",
+                None,
+                &ProcessOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.summary, None);
+    }
+
+    #[tokio::test]
+    async fn test_stop_conditions_follow_the_path() {
+        let brain = Brain::new(BrainConfig {
+            stop_conditions: HashMap::from([(
+                Intent::QuickAction,
+                StopConditions {
+                    stop: Vec::new(),
+                    max_tokens: Some(2),
+                },
+            )]),
+            ..Default::default()
+        });
+
+        let response = brain
+            .process(
+                "What time is it in Paris?",
+                None,
+                &ProcessOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.content, "[FAST] Processed: ");
+        assert!(response
+            .reasoning_trace
+            .unwrap()
+            .contains("Generation stopped at max_tokens 2"));
+
+        let strategy = ProcessOptions {
+            forced: Some(Intent::Strategy),
+            ..Default::default()
+        };
+        let response = brain
+            .process("What time is it in Paris?", None, &strategy)
+            .await
+            .unwrap();
+        assert!(response.content.ends_with("Paris?"));
+    }
+
+    #[tokio::test]
+    async fn test_fast_model_pool_is_weighted() {
+        let brain = Brain::new(BrainConfig {
+            fast_model: "fallback".to_string(),
+            slow_model: "deep".to_string(),
+            fast_model_pool: HashMap::from([
+                ("quick".to_string(), 3),
+                ("careful".to_string(), 1),
+                ("retired".to_string(), 0),
+            ]),
+            ..Default::default()
+        });
+
+        let picks: Vec<String> = (0..200)
+            .map(|seed| brain.model_for(&Intent::QuickAction, seed))
+            .collect();
+        let quick = picks.iter().filter(|m| *m == "quick").count();
+        let careful = picks.iter().filter(|m| *m == "careful").count();
+        assert_eq!(quick + careful, picks.len());
+        assert!(quick > careful && careful > 0);
+        assert_eq!(brain.model_for(&Intent::Strategy, 7), "deep");
+
+        let options = ProcessOptions {
+            seed: Some(7),
+            ..Default::default()
+        };
+        let response = brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.model,
+            Some(brain.model_for(&Intent::QuickAction, 7))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_calibrated_probabilities() {
+        let calibration = Calibration { temperature: 2.0 };
+        let probabilities = calibration.probabilities(&Intent::Strategy, 0.9);
+        let total: f32 = probabilities.values().sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        // Softened, but still the favourite
+        assert!(probabilities[&Intent::Strategy] < 0.9);
+        assert!(probabilities[&Intent::Strategy] > probabilities[&Intent::QuickAction]);
+
+        let brain = Brain::new(BrainConfig {
+            calibration: Some(calibration),
+            ..Default::default()
+        });
+        let response = brain
+            .process("Plan our growth strategy", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        let probabilities = response.routing.probabilities.unwrap();
+        assert_eq!(
+            response.routing.confidence,
+            probabilities[&Intent::Strategy]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fit_calibration_softens_overconfidence() {
+        // The classifier is sure of each of these but right only half the time
+        let examples = vec![
+            ("Plan the growth strategy".to_string(), Intent::Strategy),
+            ("Plan the growth strategy ".to_string(), Intent::QuickAction),
+            ("Plan a strategy for hiring".to_string(), Intent::Strategy),
+            (
+                "Plan a strategy for hiring ".to_string(),
+                Intent::QuickAction,
+            ),
+        ];
+        let brain = Brain::new(BrainConfig::default());
+        assert!(brain.fit_calibration(&examples).await.temperature > 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_usage_is_estimated() {
+        let brain = Brain::new(BrainConfig::default());
+        let response = brain
+            .process("What time is it?", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        let usage = response.usage.unwrap();
+        assert!(usage.estimated);
+        assert!(usage.prompt_tokens > 0 && usage.completion_tokens > 0);
+    }
+
+    #[tokio::test]
+    async fn test_long_inputs_are_map_reduced() {
+        let brain = Brain::new(BrainConfig {
+            chunk_input_tokens: Some(20),
+            ..Default::default()
+        });
+        let document = "Quarterly numbers for the northern region were flat. ".repeat(6);
+
+        let response = brain
+            .process(&document, None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.routing.final_intent, Intent::Strategy);
+        assert!(response.content.contains("Part 1/"));
+        let trace = response.reasoning_trace.unwrap();
+        assert!(trace.contains("Map-reduce: 4 chunk(s) answered on the fast model"));
+
+        let response = brain
+            .process("What time is it?", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert!(!response.content.contains("Part 1/"));
+    }
+
+    #[tokio::test]
+    async fn test_style_guide_is_gated_per_intent() {
+        let brain = Brain::new(BrainConfig {
+            style_guide: Some("formal, concise, bulleted".to_string()),
+            style_by_intent: HashMap::from([(Intent::Strategy, false)]),
+            ..Default::default()
+        });
+
+        let response = brain
+            .process("What time is it?", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert!(response.content.starts_with("[styled] "));
+
+        let strategy = ProcessOptions {
+            forced: Some(Intent::Strategy),
+            ..Default::default()
+        };
+        let response = brain
+            .process("What time is it?", None, &strategy)
+            .await
+            .unwrap();
+        assert!(!response.content.starts_with("[styled] "));
+    }
+
+    #[tokio::test]
+    async fn test_include_config_reports_generation_settings() {
+        let brain = Brain::new(BrainConfig {
+            knowledge_top_k: Some(5),
+            slow_model: "deep".to_string(),
+            style_guide: Some("concise".to_string()),
+            style_by_intent: HashMap::from([(Intent::Strategy, false)]),
+            ..Default::default()
+        });
+
+        let response = brain
+            .process("Plan our growth strategy", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert!(response.effective_config.is_none());
+
+        let options = ProcessOptions {
+            include_config: true,
+            seed: Some(7),
+            persona: Some("legal".to_string()),
+            ..Default::default()
+        };
+        let config = brain
+            .process("Plan our growth strategy", None, &options)
+            .await
+            .unwrap()
+            .effective_config
+            .unwrap();
+        assert_eq!(config.intent, Intent::Strategy);
+        assert_eq!(config.model, "deep");
+        assert_eq!((config.seed, config.knowledge_top_k), (7, 5));
+        // No such persona in the index, so none was used
+        assert_eq!(config.persona, None);
+        assert_eq!(config.style_guide, None);
+    }
+
+    #[tokio::test]
+    async fn test_include_raw_returns_unprocessed_output() {
+        let brain = Brain::new(BrainConfig {
+            style_guide: Some("concise".to_string()),
+            max_response_bytes: Some(20),
+            ..Default::default()
+        });
+
+        let options = ProcessOptions {
+            include_raw: true,
+            ..Default::default()
+        };
+        let response = brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.raw_content.as_deref(),
+            Some("[FAST] Processed: What time is it?")
+        );
+        assert!(response.truncated);
+
+        let response = brain
+            .process("What time is it?", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.raw_content, None);
+    }
+
+    #[tokio::test]
+    async fn test_sub_brain_fails_over_to_next_region() {
+        use axum::{routing::post, Json, Router};
+
+        // A healthy replica that answers with a brain of its own
+        let remote = Arc::new(Brain::new(BrainConfig::default()));
+        let app = Router::new().route(
+            "/process",
+            post(move |Json(body): Json<Value>| {
+                let remote = remote.clone();
+                async move {
+                    let input = body["input"].as_str().unwrap_or_default().to_string();
+                    Json(
+                        remote
+                            .process(&input, None, &ProcessOptions::default())
+                            .await
+                            .unwrap(),
+                    )
+                }
+            }),
+        );
+        let healthy = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let healthy_addr = healthy.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(healthy, app).await.unwrap() });
+
+        // Nothing listens on the local region's port
+        let down_addr = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let brain = Brain::new(BrainConfig {
+            region: Some("us-east".to_string()),
+            sub_brain_regions: HashMap::from([(
+                Intent::QuickAction,
+                vec![
+                    RegionalBackend {
+                        region: "eu-west".to_string(),
+                        url: format!("http://{}", healthy_addr),
+                    },
+                    RegionalBackend {
+                        region: "us-east".to_string(),
+                        url: format!("http://{}", down_addr),
+                    },
+                ],
+            )]),
+            ..Default::default()
+        });
+
+        let response = brain
+            .process("What time is it?", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(response.system, "cortex@eu-west");
+        assert!(response.routing.reason.contains("failed over from us-east"));
+    }
+
+    #[tokio::test]
+    async fn test_thinking_status_precedes_deep_answers() {
+        let brain = Brain::new(BrainConfig {
+            thinking_message: Some("Analyzing strategy…".to_string()),
+            ..Default::default()
+        });
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        brain
+            .process_streaming(
+                "Plan our growth strategy",
+                None,
+                &ProcessOptions::default(),
+                tx,
+            )
+            .await;
+        match rx.recv().await {
+            Some(StreamEvent::Status(message)) => assert_eq!(message, "Analyzing strategy…"),
+            other => panic!("expected a status frame first, got {:?}", other),
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        brain
+            .process_streaming("What time is it?", None, &ProcessOptions::default(), tx)
+            .await;
+        assert!(!matches!(rx.recv().await, Some(StreamEvent::Status(_))));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_stream_aborts() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        let brain = Brain::new(BrainConfig {
+            sub_brains: HashMap::from([(Intent::QuickAction, format!("http://{}", addr))]),
+            stream_idle_timeout_ms: Some(50),
+            ..Default::default()
+        });
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+        brain
+            .process_streaming("What time is it?", None, &ProcessOptions::default(), tx)
+            .await;
+        match rx.recv().await {
+            Some(StreamEvent::Error(e)) => assert!(e.starts_with("generation stalled")),
+            other => panic!("expected a stall error, got {:?}", other),
+        }
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_complexity_routes_deep_without_keywords() {
+        let simple = "What time is it?";
+        let complex = "First, tell me which of our three suppliers is cheapest, and if that one is late \
+                       (is it ever?), which is the backup? Then draft an email to them, also cc finance, \
+                       because the contract renews next month.";
+        assert!(complexity(simple) < 0.1);
+        assert!(complexity(complex) > 0.6);
+
+        let classifier = IntentClassifier::new(None, None, Some(0.6));
+        let (intent, reason, _) = classifier.explain(complex, None).await;
+        assert_eq!(intent, Intent::Strategy);
+        assert!(reason.contains("overridden: complexity"));
+        assert_eq!(classifier.classify(simple).await, Intent::QuickAction);
+    }
+
+    #[tokio::test]
+    async fn test_entities_bias_classification() {
+        let input = "What should we do with $40k before March?";
+        let classifier = IntentClassifier::new(None, Some(EntityRules::default()), None);
+        let (intent, reason, confidence) = classifier.explain(input, None).await;
+        assert_eq!(intent, Intent::Strategy);
+        assert_eq!(
+            reason,
+            "keyword match 'what' overridden: entities (money, date) score +1.0"
+        );
+        assert_eq!(confidence, 1.0);
+
+        let keywords_only = IntentClassifier::new(None, None, None);
+        assert_eq!(keywords_only.classify(input).await, Intent::QuickAction);
+    }
+
+    #[tokio::test]
+    async fn test_voice_transcripts_classify_with_restored_punctuation() {
+        let transcript = "um can you walk me through the quarterly numbers for the eastern region";
+        let voice = ProcessOptions {
+            source: InputSource::Voice,
+            ..Default::default()
+        };
+        let config = BrainConfig {
+            structural_weights: Some(StructuralWeights::default()),
+            voice_preprocessing: Some(VoicePreprocessing::Classify),
+            ..Default::default()
+        };
+
+        let brain = Brain::new(config.clone());
+        let report = brain
+            .dry_run(transcript, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.routing.classified_intent, Intent::Unknown);
+        let report = brain.dry_run(transcript, &voice).await.unwrap();
+        assert_eq!(report.routing.classified_intent, Intent::QuickAction);
+        assert!(report.routing.reason.contains("structure (question)"));
+        assert!(report.prompt.unwrap().contains(transcript));
+
+        let brain = Brain::new(BrainConfig {
+            voice_preprocessing: Some(VoicePreprocessing::Restore),
+            ..config
+        });
+        let report = brain.dry_run(transcript, &voice).await.unwrap();
+        assert!(report
+            .prompt
+            .unwrap()
+            .contains("Can you walk me through the quarterly numbers for the eastern region?"));
+    }
+
+    #[tokio::test]
+    async fn test_answer_confidence_is_estimated_on_request() {
+        let brain = Brain::new(BrainConfig::default());
+        assert_eq!(
+            brain
+                .process_directive("What time is it?")
+                .await
+                .unwrap()
+                .answer_confidence,
+            None
+        );
+
+        let brain = Brain::new(BrainConfig {
+            estimate_answer_confidence: true,
+            ..Default::default()
+        });
+        let response = brain.process_directive("What time is it?").await.unwrap();
+        assert_eq!(response.answer_confidence, Some(1.0));
+        let response = brain
+            .process_directive("Maybe it's noon, probably?")
+            .await
+            .unwrap();
+        assert_eq!(response.answer_confidence, Some(0.7));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_persona_falls_back_or_fails() {
+        let mut config = BrainConfig {
+            fallback_persona: Some("support".to_string()),
+            ..Default::default()
+        };
+        let legal = ProcessOptions {
+            persona: Some("legal".to_string()),
+            ..Default::default()
+        };
+
+        let response = Brain::new(config.clone())
+            .process("What time is it?", None, &legal)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.warnings,
+            vec!["unknown persona 'legal'; used 'support' instead"]
+        );
+
+        config.strict_personas = true;
+        let err = Brain::new(config)
+            .process("What time is it?", None, &legal)
+            .await
+            .unwrap_err();
+        assert!(err.message.starts_with("unknown persona 'legal'"));
+        assert_eq!((err.phase, err.retryable), (Phase::Request, false));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_answers_are_regenerated() {
+        let brain = Brain::new(BrainConfig {
+            repetition_threshold: Some(0.9),
+            ..Default::default()
+        });
+        let first = brain
+            .process("What time is it?", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+
+        let options = ProcessOptions {
+            recent_answers: vec![first.content.clone()],
+            ..Default::default()
+        };
+        let second = brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap();
+        assert_ne!(second.content, first.content);
+        assert!(second
+            .reasoning_trace
+            .unwrap()
+            .contains("Repetition check: overlap 1.00"));
+
+        // The regeneration takes the same fast path, self-check included
+        let brain = Brain::new(BrainConfig {
+            repetition_threshold: Some(0.9),
+            fast_self_check: true,
+            ..Default::default()
+        });
+        let third = brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap();
+        let trace = third.reasoning_trace.unwrap();
+        assert!(trace.contains("Self-check: passed"));
+        assert!(trace.contains("regenerated once for a different perspective"));
+    }
+
+    #[tokio::test]
+    async fn test_conversation_token_budget() {
+        let brain = Brain::new(BrainConfig {
+            conversation_token_budget: Some(40),
+            ..Default::default()
+        });
+        let options = ProcessOptions {
+            conversation_id: Some("c1".to_string()),
+            ..Default::default()
+        };
+
+        let first = brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap();
+        let used = first
+            .usage
+            .map(|u| u.prompt_tokens + u.completion_tokens)
+            .unwrap();
+        assert_eq!(first.token_budget_remaining, Some(40 - used));
+
+        let mut turns = 1;
+        while brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap()
+            .system
+            != "budget_exhausted"
+        {
+            turns += 1;
+        }
+        assert_eq!(turns as u64, 40u64.div_ceil(used));
+
+        // Other conversations keep their own budget
+        let other = ProcessOptions {
+            conversation_id: Some("c2".to_string()),
+            ..Default::default()
+        };
+        assert_ne!(
+            brain
+                .process("And now?", None, &other)
+                .await
+                .unwrap()
+                .system,
+            "budget_exhausted"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_classification_sink_receives_events() {
+        struct Collect(tokio::sync::mpsc::UnboundedSender<ClassificationEvent>);
+
+        #[async_trait::async_trait]
+        impl ClassificationSink for Collect {
+            async fn record(&self, event: ClassificationEvent) {
+                let _ = self.0.send(event);
+            }
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let brain =
+            Brain::new(BrainConfig::default()).with_classification_sink(Arc::new(Collect(tx)));
+        let response = brain
+            .process("Draft a roadmap", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.request_id, response.request_id);
+        assert_eq!(event.input_hash, response.input_hash);
+        assert_eq!(event.intent, Intent::Strategy);
+        assert_eq!(event.features, vec!["keyword match 'roadmap'"]);
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_prompt_includes_previous_answer() {
+        let brain = Brain::new(BrainConfig::default());
+        let options = ProcessOptions {
+            previous_answer: Some("Focus on pricing first.".to_string()),
+            ..Default::default()
+        };
+        for directive in ["Draft a roadmap", "What time is it?"] {
+            // The mocked models echo the prompt they were sent
+            let received = brain
+                .process(directive, None, &options)
+                .await
+                .unwrap()
+                .content;
+            assert!(received.contains(REGENERATE_INSTRUCTION));
+            assert!(received.contains("Focus on pricing first."));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fast_self_check_feeds_the_critique_back() {
+        let brain = Brain::new(BrainConfig {
+            fast_self_check: true,
+            ..Default::default()
+        });
+
+        let response = brain.process_directive("What time is it?").await.unwrap();
+        assert!(response
+            .reasoning_trace
+            .unwrap()
+            .contains("Self-check: passed"));
+
+        let response = brain
+            .process_directive("What is still TODO?")
+            .await
+            .unwrap();
+        assert!(response.reasoning_trace.unwrap().contains(
+            "Self-check flagged \"answer contains a TODO placeholder\"; regenerated once"
+        ));
+        // The mocked fast model echoes the prompt it was sent
+        assert!(response.content.contains(SELF_CHECK_INSTRUCTION));
+        assert!(response
+            .content
+            .contains("answer contains a TODO placeholder"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_loop_is_bounded() {
+        struct Named(&'static str);
+
+        #[async_trait::async_trait]
+        impl Tool for Named {
+            fn name(&self) -> &str {
+                self.0
+            }
+
+            fn description(&self) -> &str {
+                "looks things up"
+            }
+
+            async fn call(&self, _input: &str) -> Result<String, String> {
+                Ok(format!("{} result", self.0))
+            }
+        }
+
+        let options = ProcessOptions {
+            forced: Some(Intent::QuickAction),
+            ..Default::default()
+        };
+        let brain = Brain::new(BrainConfig {
+            max_tool_iterations: Some(1),
+            ..Default::default()
+        })
+        .with_tool(Arc::new(Named("weather")))
+        .with_tool(Arc::new(Named("clock")));
+        let response = brain
+            .process("Check the weather and the clock", None, &options)
+            .await
+            .unwrap();
+
+        let trace = response.reasoning_trace.unwrap();
+        assert!(trace
+            .contains("Tool call 1: weather(Check the weather and the clock) -> weather result"));
+        assert!(trace.contains("Tool loop stopped after 1 call(s)"));
+        assert!(!trace.contains("clock result"));
+        assert!(response
+            .content
+            .contains("Tool weather returned: weather result"));
+    }
+
+    #[tokio::test]
+    async fn test_unsure_tool_calls_are_suppressed() {
+        struct Weather;
+
+        #[async_trait::async_trait]
+        impl Tool for Weather {
+            fn name(&self) -> &str {
+                "weather"
+            }
+
+            fn description(&self) -> &str {
+                "looks up the weather"
+            }
+
+            async fn call(&self, _input: &str) -> Result<String, String> {
+                Ok("sunny".to_string())
+            }
+        }
+
+        let options = ProcessOptions {
+            forced: Some(Intent::QuickAction),
+            ..Default::default()
+        };
+        let brain = Brain::new(BrainConfig {
+            min_tool_confidence: Some(0.8),
+            ..Default::default()
+        })
+        .with_tool(Arc::new(Weather));
+
+        let response = brain
+            .process("Check the weather in Berlin", None, &options)
+            .await
+            .unwrap();
+        assert!(response.content.contains("Tool weather returned: sunny"));
+
+        // Two hedges in the step put its confidence at 0.70
+        let unsure = "Maybe check the weather, I think it rained";
+        let response = brain.process(unsure, None, &options).await.unwrap();
+        assert!(!response.content.contains("Tool weather returned"));
+        assert!(response.reasoning_trace.unwrap().contains(&format!(
+            "Tool call weather({}) suppressed: confidence 0.70 below min_tool_confidence 0.8",
+            unsure
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_watermark_carries_request_id() {
+        let brain = Brain::new(BrainConfig {
+            watermark: Some(WatermarkStyle::Invisible),
+            ..Default::default()
+        });
+        let response = brain
+            .process("Plan my day", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            watermark::verify(&response.content),
+            Some(response.request_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_content_keeps_its_watermark() {
+        for style in [WatermarkStyle::Invisible, WatermarkStyle::Footer] {
+            let brain = Brain::new(BrainConfig {
+                watermark: Some(style),
+                max_response_bytes: Some(1200),
+                ..Default::default()
+            });
+            let response = brain.process_directive(&"word ".repeat(400)).await.unwrap();
+            assert!(response.truncated);
+            assert!(response.content.len() <= 1200);
+            assert_eq!(
+                watermark::verify(&response.content),
+                Some(response.request_id)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_intent_default_format() {
+        let brain = Brain::new(BrainConfig {
+            intent_default_format: HashMap::from([(Intent::Strategy, Format::Markdown)]),
+            ..Default::default()
+        });
+
+        // The mocked models echo the prompt they were sent
+        let brain = &brain;
+        let received = |directive, options| async move {
+            brain
+                .process(directive, None, &options)
+                .await
+                .unwrap()
+                .content
+        };
+        let deep = received("Draft a roadmap", ProcessOptions::default()).await;
+        assert!(deep.contains(Format::Markdown.instruction()));
+
+        let code = ProcessOptions {
+            format: Some(Format::Code),
+            ..Default::default()
+        };
+        let overridden = received("Draft a roadmap", code).await;
+        assert!(overridden.contains(Format::Code.instruction()));
+        assert!(!overridden.contains(Format::Markdown.instruction()));
+
+        let fast = received("What time is it?", ProcessOptions::default()).await;
+        assert!(!fast.contains(Format::Markdown.instruction()));
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_and_answer_budgets_bound_each_phase() {
+        let brain = Brain::new(BrainConfig {
+            reasoning_token_budget: Some(8),
+            answer_token_budget: Some(10),
+            ..Default::default()
+        });
+        let response = brain
+            .process_directive("Draft a roadmap for the next two quarters")
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "[DEEP] Analyzing strategy for: Draft a");
+        assert!(prompt::estimate_tokens(&response.content) <= 10);
+        let trace = response.reasoning_trace.unwrap();
+        assert!(trace.starts_with("1. Loaded 0 knowledge chunk(s)"));
+        assert!(trace.contains("Tokens used: reasoning 8/8, answer 10/10"));
+    }
+
+    #[tokio::test]
+    async fn test_feature_log_records_features_and_corrections() {
+        let path =
+            std::env::temp_dir().join(format!("aegnt-features-{}.jsonl", uuid::Uuid::new_v4()));
+        let brain = Brain::new(BrainConfig {
+            classification_feature_log: Some(path.clone()),
+            ..BrainConfig::default()
+        });
+        let response = brain
+            .process_directive("What is our Q3 roadmap?")
+            .await
+            .unwrap();
+        brain
+            .record_correction(&response.request_id, Intent::QuickAction)
+            .await
+            .unwrap();
+        // The features are appended off the request path
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let entries: Vec<FeatureLogEntry> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(entries.len(), 2);
+        let features = entries
+            .iter()
+            .find_map(|entry| match entry {
+                FeatureLogEntry::Features(record) => Some(record),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(features.request_id, response.request_id);
+        assert_eq!(features.strategy_keywords, vec!["roadmap"]);
+        assert_eq!(features.quick_keywords, vec!["what"]);
+        assert_eq!(features.structural_cues, vec!["question"]);
+        assert_eq!(features.entity_kinds, vec![entities::EntityKind::Date]);
+        assert_eq!(features.words, 5);
+        assert_eq!(features.final_intent, response.routing.final_intent);
+        assert!(entries.contains(&FeatureLogEntry::Correction {
+            request_id: response.request_id.clone(),
+            intent: Intent::QuickAction,
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_slow_deep_path_degrades_to_fast_answer() {
+        let brain = Brain::new(BrainConfig {
+            soft_timeout_ms: Some(20),
+            ..Default::default()
+        });
+        let mut routing = RoutingDecision {
+            final_intent: Intent::Strategy,
+            ..Default::default()
+        };
+        let slow = async {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            brain.deep_reason("Draft a roadmap", None, &[], 1).await
+        };
+        let started = std::time::Instant::now();
+        let (system, content, trace) = brain
+            .within_soft_timeout(slow, "Draft a roadmap", None, 1, &mut routing)
+            .await;
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert!(routing.downgraded);
+        assert_eq!(routing.final_intent, Intent::QuickAction);
+        assert_eq!(routing.reason, "deep path exceeded 20ms soft timeout");
+        assert_eq!(system, "cortex");
+        assert_eq!(content, "[FAST] Processed: Draft a roadmap");
+        assert!(trace.unwrap().starts_with(
+            "Degraded: deep model exceeded soft timeout of 20ms, answered with fast model"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_slow_classifier_falls_back_to_built_in_rules() {
+        struct Slow;
+
+        #[async_trait::async_trait]
+        impl Classifier for Slow {
+            async fn classify(&self, _input: &str) -> (Intent, String, f32) {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                (Intent::QuickAction, "model".to_string(), 0.9)
+            }
+        }
+
+        let brain = Brain::new(BrainConfig {
+            classification_timeout_ms: Some(20),
+            ..Default::default()
+        })
+        .with_classifier(Arc::new(Slow));
+        let started = std::time::Instant::now();
+        let report = brain
+            .dry_run("Draft a roadmap", &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(report.routing.classified_intent, Intent::Strategy);
+        assert_eq!(
+            report.routing.reason,
+            "keyword match 'roadmap'; classifier timed out after 20ms, fell back to built-in rules"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_requests_keep_their_own_ids() {
+        // Slow enough that the second request arrives while the first runs
+        struct Slow;
+
+        #[async_trait::async_trait]
+        impl Classifier for Slow {
+            async fn classify(&self, _input: &str) -> (Intent, String, f32) {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                (Intent::QuickAction, "model".to_string(), 0.9)
+            }
+        }
+
+        let brain = Brain::new(BrainConfig {
+            coalesce_requests: true,
+            signing_key: Some("secret".to_string()),
+            watermark: Some(WatermarkStyle::Footer),
+            ..Default::default()
+        })
+        .with_classifier(Arc::new(Slow));
+        let options = |id: &str| ProcessOptions {
+            request_id: Some(id.to_string()),
+            ..Default::default()
+        };
+        let (first_options, second_options) = (options("first"), options("second"));
+        let (first, second) = tokio::join!(
+            brain.process("What time is it?", None, &first_options),
+            brain.process("What time is it?", None, &second_options),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+
+        let shared = [&first, &second]
+            .iter()
+            .filter(|r| {
+                r.routing
+                    .reason
+                    .contains("coalesced with an identical in-flight request")
+            })
+            .count();
+        assert_eq!(shared, 1);
+        for (response, id) in [(&first, "first"), (&second, "second")] {
+            assert_eq!(response.request_id, id);
+            assert_eq!(watermark::verify(&response.content).as_deref(), Some(id));
+            assert!(BrainClient::verify(response, "secret"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_intent_scoped_knowledge_grounds_its_intent() {
+        let dir = std::env::temp_dir().join(format!("aegnt-scoped-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("ops.md"),
+            "---\nintents: [QuickAction]\n---\nDeploys run nightly.",
+        )
+        .unwrap();
+        let brain = Brain::new(BrainConfig {
+            knowledge_path: dir.clone(),
+            ..Default::default()
+        });
+        brain.reindex_knowledge().await.unwrap().await.unwrap();
+
+        let report = brain
+            .dry_run("When do deploys run?", &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.routing.final_intent, Intent::QuickAction);
+        assert_eq!(report.knowledge.len(), 1);
+        assert_eq!(report.knowledge[0].chunk.source, "ops.md");
+
+        let report = brain
+            .dry_run("Plan when deploys run", &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.routing.final_intent, Intent::Strategy);
+        assert!(report.knowledge.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_chunk_filters_bound_injected_knowledge() {
+        let dir = std::env::temp_dir().join(format!("aegnt-filters-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, text) in [
+            ("a.md", "Enterprise pricing plan."),
+            ("b.md", "Enterprise support hours."),
+            ("c.md", "Pricing for startups."),
+        ] {
+            std::fs::write(dir.join(file), text).unwrap();
+        }
+        let injected = |min_chunk_score, max_injected_chunks| {
+            let dir = dir.clone();
+            async move {
+                let brain = Brain::new(BrainConfig {
+                    knowledge_path: dir,
+                    min_chunk_score,
+                    max_injected_chunks,
+                    ..Default::default()
+                });
+                brain.reindex_knowledge().await.unwrap().await.unwrap();
+                let report = brain
+                    .dry_run("Plan pricing for enterprise", &ProcessOptions::default())
+                    .await
+                    .unwrap();
+                let sources: Vec<String> = report
+                    .knowledge
+                    .iter()
+                    .map(|c| c.chunk.source.clone())
+                    .collect();
+                (sources, report.retrieved)
+            }
+        };
+
+        // Scores are 0.75, 0.5 and 0.25 of the query's terms
+        assert_eq!(
+            injected(None, None).await,
+            (vec!["a.md".into(), "c.md".into(), "b.md".into()], 3)
+        );
+        assert_eq!(
+            injected(Some(0.5), None).await,
+            (vec!["a.md".into(), "c.md".into()], 3)
+        );
+        assert_eq!(injected(None, Some(1)).await, (vec!["a.md".into()], 3));
+        assert_eq!(injected(Some(0.9), Some(1)).await, (vec![], 3));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_rerank_reorders_a_widened_candidate_set() {
+        let dir = std::env::temp_dir().join(format!("aegnt-rerank-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, text) in [
+            (
+                "broad.md",
+                "Enterprise pricing plan for teams with many seats and custom contracts.",
+            ),
+            ("focused.md", "Enterprise pricing."),
+            ("support.md", "Enterprise support hours and holidays."),
+        ] {
+            std::fs::write(dir.join(file), text).unwrap();
+        }
+        let injected = |rerank, knowledge_top_k, rerank_candidates| {
+            let dir = dir.clone();
+            async move {
+                let brain = Brain::new(BrainConfig {
+                    knowledge_path: dir,
+                    rerank,
+                    knowledge_top_k: Some(knowledge_top_k),
+                    rerank_candidates,
+                    ..Default::default()
+                });
+                brain.reindex_knowledge().await.unwrap().await.unwrap();
+                let report = brain
+                    .dry_run("Plan pricing for enterprise", &ProcessOptions::default())
+                    .await
+                    .unwrap();
+                let chunks: Vec<(String, Option<f32>)> = report
+                    .knowledge
+                    .iter()
+                    .map(|c| (c.chunk.source.clone(), c.rerank_score))
+                    .collect();
+                (chunks, report.retrieved)
+            }
+        };
+
+        // Retrieval favours the passage matching most of the query
+        let (chunks, retrieved) = injected(false, 2, None).await;
+        assert_eq!(
+            chunks,
+            vec![
+                ("broad.md".to_string(), None),
+                ("focused.md".to_string(), None)
+            ]
+        );
+        assert_eq!(retrieved, 2);
+
+        // The cross-encoder favours the focused one, picked from the
+        // default 10 candidates rather than the top 1
+        let (chunks, retrieved) = injected(true, 1, None).await;
+        assert_eq!(chunks, vec![("focused.md".to_string(), Some(1.0))]);
+        assert_eq!(retrieved, 3);
+
+        // Candidates are never fewer than knowledge_top_k
+        let (chunks, retrieved) = injected(true, 2, Some(1)).await;
+        assert_eq!(retrieved, 2);
+        assert_eq!(chunks[0], ("focused.md".to_string(), Some(1.0)));
+        assert_eq!(chunks[1].0, "broad.md");
+        assert!((chunks[1].1.unwrap() - 4.0 / 11.0).abs() < 1e-6);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_budget_bounds_what_the_model_receives() {
+        let dir = std::env::temp_dir().join(format!("aegnt-budget-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("ops.md"),
+            "---\nintents: [QuickAction]\n---\nDeploys run nightly.",
+        )
+        .unwrap();
+        let template = dir.join("template.txt");
+        std::fs::write(
+            &template,
+            format!("{}{{{{system}}}}{{{{user}}}}", "pad ".repeat(10)),
+        )
+        .unwrap();
+        let config = BrainConfig {
+            knowledge_path: dir.clone(),
+            max_prompt_tokens: Some(6),
+            ..Default::default()
+        };
+
+        // The mocked fast model echoes the prompt it was sent
+        let brain = Brain::new(config.clone());
+        brain.reindex_knowledge().await.unwrap().await.unwrap();
+        let response = brain
+            .process("When do deploys run?", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert!(!response.content.contains("Deploys run nightly."));
+        assert!(response
+            .reasoning_trace
+            .unwrap()
+            .contains("Prompt trimmed to fit budget: dropped 1 knowledge chunk(s)"));
+
+        let brain = Brain::new(BrainConfig {
+            prompt_templates: HashMap::from([(config.fast_model.clone(), template)]),
+            ..config
+        });
+        brain.load_prompt_templates().await.unwrap();
+        let err = brain
+            .process("When do deploys run?", None, &ProcessOptions::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.category, ErrorCategory::PromptTooLarge);
+        assert!(err.message.contains("templated prompt"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_routing_decision() {
+        let brain = Brain::new(BrainConfig::default());
+
+        let response = brain.process_directive("Draft a roadmap").await.unwrap();
+        assert_eq!(response.routing.classified_intent, Intent::Strategy);
+        assert_eq!(response.routing.reason, "keyword match 'roadmap'");
+
+        let rambling =
+            "Tell me everything you can remember about the weather last Tuesday afternoon";
+        let response = brain.process_directive(rambling).await.unwrap();
+        assert_eq!(response.routing.classified_intent, Intent::Unknown);
+        assert_eq!(response.routing.final_intent, Intent::QuickAction);
+    }
+
+    #[test]
+    fn test_redact_masks_sensitive_fragments() {
+        let sensitive = vec![
+            "\"knowledge\"".to_string(),
+            "a.md#0".to_string(),
+            "a.md#10".to_string(),
+        ];
+        let trace = "1. Loaded 2 knowledge chunk(s) from \"knowledge\" [a.md#0, a.md#10]";
+
+        assert_eq!(
+            redact(trace, &sensitive),
+            "1. Loaded 2 knowledge chunk(s) from [redacted] [[redacted], [redacted]]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_translates_foreign_language_responses() {
+        let brain = Brain::new(BrainConfig {
+            translation_model: Some("ollama:aya".to_string()),
+            ..Default::default()
+        });
+
+        let response = brain
+            .process_directive(
+                "Wie spät ist es heute in Berlin und wann beginnt das Treffen der Abteilung?",
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.language.as_deref(), Some("deu"));
+        assert!(response.original_content.unwrap().starts_with("[FAST]"));
+        assert!(response.content.starts_with("[eng->deu]"));
+
+        let response = brain
+            .process_directive("What time is it in Berlin today?")
+            .await
+            .unwrap();
+        assert_eq!(response.language, None);
+    }
+
+    #[test]
+    fn test_strategy_marker_needs_length_and_keyword() {
+        let answer = "First outline a phased roadmap, then staff each phase.";
+
+        assert_eq!(strategy_marker(answer, 20), Some("roadmap"));
+        assert_eq!(strategy_marker(answer, 200), None);
+        assert_eq!(
+            strategy_marker("It is five past ten in the morning.", 20),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_plan() {
+        let brain = Brain::new(BrainConfig {
+            slow_model: "deep-model".to_string(),
+            ..Default::default()
+        });
+
+        let report = brain
+            .dry_run("Draft a roadmap", &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.path, ExecutionPath::Deep);
+        assert_eq!(report.model.as_deref(), Some("deep-model"));
+        assert_eq!(report.prompt.as_deref(), Some("Draft a roadmap"));
+
+        let rambling =
+            "Tell me everything you can remember about the weather last Tuesday afternoon";
+        let report = brain
+            .dry_run(rambling, &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.path, ExecutionPath::Fast);
+        assert_eq!(report.routing.final_intent, Intent::QuickAction);
+    }
+
+    #[tokio::test]
+    async fn test_control_directives_bypass_models() {
+        let brain = Brain::new(BrainConfig {
+            control_prefix: Some("::".to_string()),
+            ..Default::default()
+        });
+
+        let response = brain.process_directive("::reload templates").await.unwrap();
+        assert_eq!(response.system, "control");
+        assert_eq!(response.content, "Reloaded 0 prompt template(s)");
+
+        assert!(brain.process_directive("::clear context").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_classification_overrides_win_and_reload() {
+        let path =
+            std::env::temp_dir().join(format!("aegnt-overrides-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{"Plan my day": "QuickAction"}"#).unwrap();
+        let brain = Brain::new(BrainConfig {
+            classification_overrides: Some(path.clone()),
+            control_prefix: Some("::".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(brain.load_classification_overrides().await, Ok(1));
+
+        let report = brain
+            .dry_run(" Plan my day ", &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.routing.classified_intent, Intent::QuickAction);
+        assert_eq!(report.routing.confidence, 1.0);
+
+        std::fs::write(&path, r#"{"Plan my day": "Strategy", "hi": "QuickAction"}"#).unwrap();
+        let response = brain.process_directive("::reload overrides").await.unwrap();
+        assert_eq!(response.content, "Reloaded 2 classification override(s)");
+        let report = brain
+            .dry_run("Plan my day", &ProcessOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.routing.classified_intent, Intent::Strategy);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_confidence_is_logged_per_intent() {
+        let brain = Brain::new(BrainConfig::default());
+        brain
+            .process("Draft a roadmap", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        brain
+            .process("Plan our growth strategy", None, &ProcessOptions::default())
+            .await
+            .unwrap();
+        brain
+            .dry_run("Draft a roadmap", &ProcessOptions::default())
+            .await
+            .unwrap();
+
+        let report = brain.confidence_report();
+        assert_eq!(report.period_secs, 3600);
+        assert_eq!(report.overall[&Intent::Strategy].count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_downgrade_under_deep_latency() {
+        let brain = Brain::new(BrainConfig {
+            adaptive_deep_p95_ms: Some(1_000),
+            ..Default::default()
+        });
+        for _ in 0..20 {
+            brain.deep_latency.record(5_000);
+        }
+
+        let response = brain.process_directive("Draft a roadmap").await.unwrap();
+        assert!(response.routing.downgraded);
+        assert_eq!(response.routing.final_intent, Intent::QuickAction);
+        assert_eq!(response.system, "cortex");
+
+        // Confident Strategy requests still take the deep path
+        let response = brain
+            .process_directive("Design a pricing strategy")
+            .await
+            .unwrap();
+        assert!(!response.routing.downgraded);
+        assert_eq!(response.system, "deep_mind");
+    }
+
+    #[tokio::test]
+    async fn test_max_response_bytes_truncates_on_char_boundary() {
+        let brain = Brain::new(BrainConfig {
+            max_response_bytes: Some(21),
+            truncation_marker: Some(String::new()),
+            ..Default::default()
+        });
+
+        // "[FAST] Processed: " is 18 bytes; each 'é' is two more
+        let response = brain.process_directive("éééé").await.unwrap();
+        assert!(response.truncated);
+        assert_eq!(response.content, "[FAST] Processed: é");
+
+        let response = brain.process_directive("é").await.unwrap();
+        assert!(!response.truncated);
+
+        let brain = Brain::new(BrainConfig {
+            max_response_bytes: Some(21),
+            truncation_marker: Some("…".to_string()),
+            ..Default::default()
+        });
+        let response = brain.process_directive("éééé").await.unwrap();
+        assert_eq!(response.content, "[FAST] Processed: …");
+
+        // A marker longer than the cap is cut to fit it
+        let brain = Brain::new(BrainConfig {
+            max_response_bytes: Some(4),
+            truncation_marker: Some("…[truncated]".to_string()),
+            ..Default::default()
+        });
+        let response = brain.process_directive("éééé").await.unwrap();
+        assert_eq!(response.content, "…[");
+        assert!(response.content.len() <= 4);
+    }
+
+    #[tokio::test]
+    async fn test_max_trace_chars_truncates_trace_only() {
+        let uncapped = Brain::new(BrainConfig::default())
+            .process_directive("Draft a roadmap")
+            .await
+            .unwrap();
+        let brain = Brain::new(BrainConfig {
+            max_trace_chars: Some(20),
+            ..Default::default()
+        });
+
+        let response = brain.process_directive("Draft a roadmap").await.unwrap();
+        let full = uncapped.reasoning_trace.unwrap();
+        let expected = format!(
+            "{}\n…[trace truncated: 20 of {} chars shown]",
+            full.chars().take(20).collect::<String>(),
+            full.chars().count()
+        );
+        assert_eq!(response.reasoning_trace.as_deref(), Some(expected.as_str()));
+        assert_eq!(response.content, uncapped.content);
+        assert!(!response.truncated);
+
+        let response = brain.process_directive("What time is it?").await.unwrap();
+        assert!(!response
+            .reasoning_trace
+            .unwrap_or_default()
+            .contains("trace truncated"));
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_on_marginal_routing() {
+        let brain = Brain::new(BrainConfig::default());
+
+        let response = brain
+            .process_directive("Pricing for enterprise?")
+            .await
+            .unwrap();
+        assert_eq!(response.routing.reason, "short input");
+        assert_eq!(response.suggestions.len(), 1);
+
+        let response = brain.process_directive("What time is it?").await.unwrap();
+        assert!(response.suggestions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trace_by_intent() {
+        let brain = Brain::new(BrainConfig {
+            trace_by_intent: HashMap::from([(Intent::Strategy, false)]),
+            ..Default::default()
+        });
+
+        let response = brain.process_directive("Draft a roadmap").await.unwrap();
+        assert_eq!(response.reasoning_trace, None);
+        assert!(response.internal_trace.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_caps_escalation() {
+        let config = BrainConfig {
+            fast_escalation_length: Some(10),
+            fast_escalation: EscalationMode::Auto,
+            ..Default::default()
+        };
+        // The fast answer echoes the context, which mentions a roadmap
+        let context = serde_json::json!({ "topic": "roadmap" });
+
+        let brain = Brain::new(config.clone());
+        let response = brain
+            .process_directive_with_context("What next?", Some(&context))
+            .await
+            .unwrap();
+        assert!(response.routing.escalated);
+
+        let brain = Brain::new(BrainConfig {
+            max_total_retries: Some(0),
+            ..config
+        });
+        let response = brain
+            .process_directive_with_context("What next?", Some(&context))
+            .await
+            .unwrap();
+        assert!(!response.routing.escalated);
+        assert!(response.routing.escalation_suggested);
+    }
+
+    #[tokio::test]
+    async fn test_seed_is_echoed() {
+        let brain = Brain::new(BrainConfig::default());
+
+        let options = ProcessOptions {
+            seed: Some(42),
+            ..Default::default()
+        };
+
+        let pinned = brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap();
+        assert_eq!(pinned.seed, 42);
+
+        let again = brain
+            .process("What time is it?", None, &options)
+            .await
+            .unwrap();
+        assert_eq!(again.content, pinned.content);
+    }
 }
@@ -1,93 +1,511 @@
+use aegnt_unltd::{
+    adaptive::AdaptiveLimit,
+    error::{ErrorCategory, Phase, ProcessError},
+    knowledge::IndexState,
+    prompt::Format,
+    queue::{BoundedQueue, Priority},
+    ramp::SlowStart,
+    rate_limit::{InMemoryStore, RateLimitStore},
+    spill::{self, SpillConfig},
+    stream::{StreamEvent, TimingRecorder},
+    structured,
+    voice::InputSource,
+    Brain, BrainConfig, ClassifierConfig, Intent, ProcessOptions, Response, RoutingDecision,
+};
 use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse,
+    },
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
+use base64::Engine;
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, sync::Arc};
-use tokio::sync::RwLock;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Intent {
-    QuickAction,
-    Strategy,
-    Unknown,
+/// Server-level settings, read from `AEGNT_*` environment variables.
+#[derive(Debug, Clone)]
+struct ServerConfig {
+    /// Length of the post-startup concurrency ramp; disabled when unset.
+    slow_start_ms: Option<u64>,
+    /// Concurrent requests allowed at the start of the ramp.
+    slow_start_initial: usize,
+    /// Concurrent requests allowed once the ramp completes, and the
+    /// ceiling for adaptive concurrency.
+    max_concurrency: usize,
+    /// Discover the concurrency limit from observed latency (AIMD) between
+    /// `min_concurrency` and `max_concurrency`.
+    adaptive_concurrency: bool,
+    min_concurrency: usize,
+    /// Fraction (0.0–1.0) of successful requests that get fully logged.
+    /// Errors and slow queries are always logged.
+    log_sample_rate: f64,
+    /// Requests at or above this latency count as slow queries.
+    slow_query_ms: u64,
+    /// Per-client requests allowed per minute on /process; unlimited when unset.
+    rate_limit_per_minute: Option<u64>,
+    /// Shared counter store so the limit holds across instances
+    /// (requires the `redis` feature).
+    redis_url: Option<String>,
+    /// Concurrent model executions; further requests queue.
+    model_slots: usize,
+    /// Requests allowed to wait for a slot; queuing is disabled when unset.
+    max_queue_depth: Option<usize>,
+    /// Longest a queued request waits before getting a 503.
+    max_queue_wait_ms: u64,
+    /// Queue load (0.0–1.0) at which responses start asking clients to back off.
+    backpressure_load: f32,
+    /// StatsD collector (`host:port`) to push metrics to (requires the
+    /// `statsd` feature).
+    statsd_addr: Option<String>,
+    #[cfg_attr(not(feature = "statsd"), allow(dead_code))]
+    statsd_prefix: String,
+    /// Directory frames a slow `/process/stream` client hasn't read yet
+    /// spill to, instead of stalling the stream; off when unset.
+    stream_spill_dir: Option<PathBuf>,
+    /// Frames held in memory per stream before spilling.
+    stream_memory_frames: usize,
+    /// Largest one stream's spill file may grow, in bytes; past it the
+    /// stream waits for the client.
+    stream_spill_max_bytes: u64,
+    /// Frames kept per stream for `/process/resume`; the oldest are dropped
+    /// past it. A finished stream keeps only its final frame.
+    resume_max_frames: usize,
+    /// Streams kept for `/process/resume`; the oldest are evicted past it.
+    resume_max_streams: usize,
+    /// Answers kept for `/process/regenerate`; the oldest are evicted past it.
+    regenerate_max_answers: usize,
+    /// JSONL file classification features are logged to for training;
+    /// off when unset.
+    classification_feature_log: Option<PathBuf>,
+    /// JSON file of `BrainConfig` fields overriding the server's defaults.
+    brain_config: Option<PathBuf>,
+    /// Key responses are signed with; overrides the config file's.
+    signing_key: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Response {
-    pub intent: Intent,
-    pub system: String,
-    pub content: String,
-    pub reasoning_trace: Option<String>,
-    pub latency_ms: u64,
+impl ServerConfig {
+    fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+
+        Self {
+            slow_start_ms: var("AEGNT_SLOW_START_MS"),
+            slow_start_initial: var("AEGNT_SLOW_START_INITIAL").unwrap_or(1),
+            max_concurrency: var("AEGNT_MAX_CONCURRENCY").unwrap_or(64),
+            adaptive_concurrency: var("AEGNT_ADAPTIVE_CONCURRENCY").unwrap_or(false),
+            min_concurrency: var("AEGNT_MIN_CONCURRENCY").unwrap_or(1),
+            log_sample_rate: var::<f64>("AEGNT_LOG_SAMPLE_RATE")
+                .unwrap_or(1.0)
+                .clamp(0.0, 1.0),
+            slow_query_ms: var("AEGNT_SLOW_QUERY_MS").unwrap_or(5000),
+            rate_limit_per_minute: var("AEGNT_RATE_LIMIT_PER_MINUTE"),
+            redis_url: var("AEGNT_REDIS_URL"),
+            model_slots: var("AEGNT_MODEL_SLOTS").unwrap_or(4),
+            max_queue_depth: var("AEGNT_MAX_QUEUE_DEPTH"),
+            max_queue_wait_ms: var("AEGNT_MAX_QUEUE_WAIT_MS").unwrap_or(10_000),
+            backpressure_load: var::<f32>("AEGNT_BACKPRESSURE_LOAD")
+                .unwrap_or(0.8)
+                .clamp(0.0, 1.0),
+            statsd_addr: var("AEGNT_STATSD_ADDR"),
+            statsd_prefix: var("AEGNT_STATSD_PREFIX").unwrap_or_else(|| "aegnt".to_string()),
+            stream_spill_dir: var("AEGNT_STREAM_SPILL_DIR"),
+            stream_memory_frames: var("AEGNT_STREAM_MEMORY_FRAMES").unwrap_or(64),
+            stream_spill_max_bytes: var("AEGNT_STREAM_SPILL_MAX_BYTES").unwrap_or(64 << 20),
+            resume_max_frames: var("AEGNT_RESUME_MAX_FRAMES").unwrap_or(256),
+            resume_max_streams: var("AEGNT_RESUME_MAX_STREAMS").unwrap_or(1024),
+            regenerate_max_answers: var("AEGNT_REGENERATE_MAX_ANSWERS").unwrap_or(1024),
+            classification_feature_log: var("AEGNT_CLASSIFICATION_FEATURE_LOG"),
+            brain_config: var("AEGNT_BRAIN_CONFIG"),
+            signing_key: var("AEGNT_SIGNING_KEY"),
+        }
+    }
+
+    /// The brain's settings: server defaults, overlaid with the fields in
+    /// `brain_config`, then with the brain settings set by variable.
+    fn brain_config(&self) -> Result<BrainConfig, String> {
+        let defaults = BrainConfig {
+            fast_model: "ollama:llama3".to_string(),
+            slow_model: "gemini-3.1-pro".to_string(),
+            memory_path: PathBuf::from("memory"),
+            knowledge_path: PathBuf::from("knowledge"),
+            ..Default::default()
+        };
+        let mut config = match &self.brain_config {
+            None => defaults,
+            Some(path) => {
+                let fail = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+                let text = std::fs::read_to_string(path).map_err(|e| fail(&e))?;
+                let overrides: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(&text).map_err(|e| fail(&e))?;
+                let mut merged = serde_json::to_value(defaults).map_err(|e| fail(&e))?;
+                if let Some(fields) = merged.as_object_mut() {
+                    fields.extend(overrides);
+                }
+                serde_json::from_value(merged).map_err(|e| fail(&e))?
+            }
+        };
+        if let Some(path) = &self.classification_feature_log {
+            config.classification_feature_log = Some(path.clone());
+        }
+        if let Some(key) = &self.signing_key {
+            config.signing_key = Some(key.clone());
+        }
+        Ok(config)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BrainConfig {
-    pub fast_model: String,
-    pub slow_model: String,
+struct AppState {
+    brain: Brain,
+    config: ServerConfig,
+    queue: Option<Arc<BoundedQueue>>,
+    adaptive: Option<Arc<AdaptiveLimit>>,
+    deep_jobs: DeepJobs,
+    streams: StreamBuffers,
+    cancellations: Cancellations,
+    answered: AnsweredRequests,
+    /// Why startup loading failed, if it did.
+    startup_error: Option<String>,
+    #[cfg(feature = "statsd")]
+    statsd: Option<aegnt_unltd::statsd::StatsdClient>,
 }
 
-pub struct Brain {
-    config: BrainConfig,
-    system_prompt: RwLock<String>,
+/// How long a streamed answer stays available to `/process/resume`.
+const RESUME_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Clone)]
+struct BufferedStream {
+    encoding: OutputEncoding,
+    frames: VecDeque<StreamEvent>,
+    /// Frames dropped from the front to stay under the per-stream cap.
+    skipped: usize,
+}
+
+/// Frames of recent `/process/stream` answers, keyed by request id, so a
+/// client whose connection dropped can recover the answer.
+struct StreamBuffers {
+    streams: Mutex<HashMap<String, (Instant, BufferedStream)>>,
+    max_frames: usize,
+    max_streams: usize,
 }
 
-impl Brain {
-    pub fn new(config: BrainConfig) -> Self {
+impl StreamBuffers {
+    fn new(max_frames: usize, max_streams: usize) -> Self {
         Self {
-            config,
-            system_prompt: RwLock::new(String::new()),
+            streams: Mutex::default(),
+            max_frames: max_frames.max(1),
+            max_streams: max_streams.max(1),
         }
     }
 
-    pub async fn process_directive(&self, input: &str) -> Response {
-        let start = std::time::Instant::now();
-        
-        let intent = classify_intent(input);
-        
-        let (system, content, reasoning_trace) = match intent {
-            Intent::QuickAction | Intent::Unknown => {
-                (format!("cortex"), format!("[CORTEX] {}\n\nI understand: {}\n\nHow would you like me to help with this?", 
-                    if input.len() < 30 { input } else { "Processing your request" }, input), None)
-            }
-            Intent::Strategy => {
-                let trace = Some("1. Intent classified as Strategy\n2. Loading knowledge base\n3. Analyzing patterns\n4. Generating strategic plan".to_string());
-                (format!("deep"), format!("[DEEP MIND] Strategic analysis: {}\n\nAnalyzing your request...\n\nI understand you're looking for a strategic approach. Let me work through this systematically.\n\nKey considerations:\n• Context: {}\n• Potential approaches: 3\n• Recommended path: Developing comprehensive strategy", input.len(), input), trace)
-            }
+    async fn start(&self, request_id: &str, encoding: OutputEncoding) {
+        let mut streams = self.streams.lock().await;
+        streams.retain(|_, (created, _)| created.elapsed() < RESUME_TTL);
+        evict_oldest(&mut streams, self.max_streams);
+        let stream = BufferedStream {
+            encoding,
+            frames: VecDeque::new(),
+            skipped: 0,
         };
-        
-        let latency_ms = start.elapsed().as_millis() as u64;
-        
-        Response {
-            intent,
-            system,
-            content,
-            reasoning_trace,
-            latency_ms,
+        streams.insert(request_id.to_string(), (Instant::now(), stream));
+    }
+
+    async fn push(&self, request_id: &str, event: StreamEvent) {
+        let mut streams = self.streams.lock().await;
+        let Some((_, stream)) = streams.get_mut(request_id) else {
+            return;
+        };
+        // The final frame (and the timing summary after it) is all a
+        // finished stream resumes to
+        let terminal = |event: &StreamEvent| {
+            matches!(
+                event,
+                StreamEvent::Done(_) | StreamEvent::Error(_) | StreamEvent::Cancelled
+            )
+        };
+        if terminal(&event) {
+            stream.skipped += stream.frames.len();
+            stream.frames.clear();
+        } else if stream.frames.len() >= self.max_frames
+            && !stream.frames.front().is_some_and(terminal)
+        {
+            stream.frames.pop_front();
+            stream.skipped += 1;
         }
+        stream.frames.push_back(event);
+    }
+
+    async fn get(&self, request_id: &str) -> Option<BufferedStream> {
+        let streams = self.streams.lock().await;
+        streams
+            .get(request_id)
+            .filter(|(created, _)| created.elapsed() < RESUME_TTL)
+            .map(|(_, stream)| stream.clone())
     }
 }
 
-fn classify_intent(input: &str) -> Intent {
-    let input_lower = input.to_lowercase();
-    
-    if input_lower.contains("plan") 
-        || input_lower.contains("strategy") 
-        || input_lower.contains("analyze")
-        || input_lower.contains("build architecture")
-        || input_lower.contains("design")
-        || input_lower.contains("roadmap")
-        || input_lower.contains("approach") {
-        Intent::Strategy
-    } else {
-        Intent::QuickAction
+/// Evicts the oldest entries until there's room for one more under `max`.
+fn evict_oldest<T>(entries: &mut HashMap<String, (Instant, T)>, max: usize) {
+    while entries.len() >= max {
+        let Some(oldest) = entries
+            .iter()
+            .min_by_key(|(_, (created, _))| *created)
+            .map(|(id, _)| id.clone())
+        else {
+            break;
+        };
+        entries.remove(&oldest);
+    }
+}
+
+/// How long an answer can be regenerated through `/process/regenerate`.
+const REGENERATE_TTL: Duration = Duration::from_secs(1800);
+
+/// A finished `/process` answer, kept so it can be regenerated.
+#[derive(Clone)]
+struct Answered {
+    payload: ProcessRequest,
+    content: String,
+    seed: u64,
+}
+
+/// Recent `/process` answers, keyed by request id.
+struct AnsweredRequests {
+    answers: Mutex<HashMap<String, (Instant, Answered)>>,
+    max_answers: usize,
+}
+
+impl AnsweredRequests {
+    fn new(max_answers: usize) -> Self {
+        Self {
+            answers: Mutex::default(),
+            max_answers: max_answers.max(1),
+        }
+    }
+
+    async fn remember(&self, payload: &ProcessRequest, response: &Response) {
+        let mut answers = self.answers.lock().await;
+        answers.retain(|_, (created, _)| created.elapsed() < REGENERATE_TTL);
+        evict_oldest(&mut answers, self.max_answers);
+        let answered = Answered {
+            payload: payload.clone(),
+            content: response.content.clone(),
+            seed: response.seed,
+        };
+        answers.insert(response.request_id.clone(), (Instant::now(), answered));
+    }
+
+    async fn get(&self, request_id: &str) -> Option<Answered> {
+        let answers = self.answers.lock().await;
+        answers
+            .get(request_id)
+            .filter(|(created, _)| created.elapsed() < REGENERATE_TTL)
+            .map(|(_, answered)| answered.clone())
+    }
+}
+
+/// Cancel switches for in-flight `/process/stream` generations, keyed by
+/// request id.
+#[derive(Default)]
+struct Cancellations {
+    pending: Mutex<HashMap<String, oneshot::Sender<()>>>,
+}
+
+impl Cancellations {
+    async fn register(&self, request_id: &str) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(request_id.to_string(), tx);
+        rx
+    }
+
+    async fn remove(&self, request_id: &str) {
+        self.pending.lock().await.remove(request_id);
+    }
+
+    /// Signals the generation for `request_id`; false if none is running.
+    async fn cancel(&self, request_id: &str) -> bool {
+        match self.pending.lock().await.remove(request_id) {
+            Some(tx) => tx.send(()).is_ok(),
+            None => false,
+        }
     }
 }
 
+/// How long an unclaimed two-phase deep result is kept.
+const DEEP_JOB_TTL: Duration = Duration::from_secs(600);
+
+enum DeepJob {
+    Pending,
+    Done(Box<ProcessResponse>),
+    Failed(ProcessFailure),
+}
+
+/// Deep-path results for `mode=two_phase` requests, keyed by handle.
+#[derive(Default)]
+struct DeepJobs {
+    jobs: Mutex<HashMap<String, (Instant, DeepJob)>>,
+}
+
+impl DeepJobs {
+    async fn start(&self) -> String {
+        let handle = uuid::Uuid::new_v4().to_string();
+        let mut jobs = self.jobs.lock().await;
+        jobs.retain(|_, (created, _)| created.elapsed() < DEEP_JOB_TTL);
+        jobs.insert(handle.clone(), (Instant::now(), DeepJob::Pending));
+        handle
+    }
+
+    async fn finish(&self, handle: &str, job: DeepJob) {
+        if let Some(entry) = self.jobs.lock().await.get_mut(handle) {
+            entry.1 = job;
+        }
+    }
+
+    /// Returns the job's state, removing it once it has completed.
+    async fn poll(&self, handle: &str) -> Option<DeepJob> {
+        let mut jobs = self.jobs.lock().await;
+        match jobs.get(handle) {
+            None => None,
+            Some((_, DeepJob::Pending)) => Some(DeepJob::Pending),
+            Some(_) => jobs.remove(handle).map(|(_, job)| job),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum InputType {
+    #[default]
+    Text,
+    Json,
+}
+
+/// How `content` is encoded in the response body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OutputEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ProcessMode {
+    #[default]
+    Single,
+    /// Answer on the fast path now; run the deep path in the background.
+    TwoPhase,
+    /// Report classification, routing, grounding and the prompt without
+    /// calling a model.
+    DryRun,
+}
+
 #[derive(Deserialize)]
+struct ProcessQuery {
+    #[serde(default)]
+    mode: ProcessMode,
+}
+
+#[derive(Clone, Deserialize)]
 struct ProcessRequest {
     input: String,
+    /// How `input` should be interpreted. `json` requires `directive_path`.
+    #[serde(default)]
+    input_type: InputType,
+    /// JSONPath to the directive text when `input_type` is `json`.
+    #[serde(default)]
+    directive_path: Option<String>,
+    #[serde(default)]
+    encoding: OutputEncoding,
+    /// Sampling seed for reproducible answers; a random one is used if unset.
+    #[serde(default)]
+    seed: Option<u64>,
+    /// Intent of the previous turn, for continuity-biased classification.
+    #[serde(default)]
+    previous_intent: Option<Intent>,
+    /// Persona whose knowledge grounds the answer, alongside shared knowledge.
+    #[serde(default)]
+    persona: Option<String>,
+    /// Also return the model's output before post-processing.
+    #[serde(default)]
+    include_raw: bool,
+    /// Also return the settings the answer was generated with.
+    #[serde(default)]
+    include_config: bool,
+    /// Stamp streamed content with its send time and end with a `timing`
+    /// frame summarizing time to first token and throughput.
+    #[serde(default)]
+    timing: bool,
+    /// Earlier answers in the conversation, so a repeat can be avoided.
+    #[serde(default)]
+    recent_answers: Vec<String>,
+    /// Conversation whose token budget the request is charged to.
+    #[serde(default)]
+    conversation_id: Option<String>,
+    /// Answer format; the intent's `intent_default_format` when unset.
+    #[serde(default)]
+    format: Option<Format>,
+    /// `voice` for speech-to-text transcripts, which `voice_preprocessing`
+    /// restores punctuation to.
+    #[serde(default)]
+    source: InputSource,
+    /// Answer being regenerated; set by `/process/regenerate` only.
+    #[serde(skip)]
+    previous_answer: Option<String>,
+}
+
+impl ProcessRequest {
+    /// Identity of the directive, ignoring how the answer is encoded.
+    fn dedup_key(&self) -> String {
+        format!(
+            "{:?}\n{}\n{:?}\n{:?}\n{:?}\n{:?}\n{}\n{}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}",
+            self.input_type,
+            self.input,
+            self.directive_path,
+            self.seed,
+            self.previous_intent,
+            self.persona,
+            self.include_raw,
+            self.include_config,
+            self.recent_answers,
+            self.conversation_id,
+            self.format,
+            self.source,
+            self.previous_answer
+        )
+    }
+
+    fn options(&self, forced: Option<Intent>) -> ProcessOptions {
+        ProcessOptions {
+            forced,
+            seed: self.seed,
+            previous_intent: self.previous_intent.clone(),
+            request_id: None,
+            persona: self.persona.clone(),
+            include_raw: self.include_raw,
+            include_config: self.include_config,
+            recent_answers: self.recent_answers.clone(),
+            conversation_id: self.conversation_id.clone(),
+            format: self.format,
+            source: self.source,
+            previous_answer: self.previous_answer.clone(),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -95,46 +513,1678 @@ struct ProcessResponse {
     intent: String,
     system: String,
     content: String,
+    truncated: bool,
+    raw_content: Option<String>,
+    action_items: Vec<String>,
+    warnings: Vec<String>,
+    effective_config: Option<aegnt_unltd::EffectiveConfig>,
+    context_used: Vec<aegnt_unltd::citations::ContextRef>,
+    model: Option<String>,
+    usage: Option<aegnt_unltd::usage::Usage>,
+    token_budget_remaining: Option<u64>,
+    summary: Option<String>,
+    grounded: Option<bool>,
+    answer_confidence: Option<f32>,
     reasoning_trace: Option<String>,
     latency_ms: u64,
+    encoding: OutputEncoding,
+    routing: RoutingDecision,
+    suggestions: Vec<String>,
+    request_id: String,
+    input_hash: String,
+    /// Signature over the UTF-8 content, before any output encoding.
+    signature: Option<String>,
+    original_content: Option<String>,
+    language: Option<String>,
+    seed: u64,
+    /// Queue load when the request was admitted; set only under backpressure.
+    load: Option<f32>,
+}
+
+/// Concurrency permits the middleware took for a request. Work that
+/// outlives the handler (a streamed or deep generation) keeps a clone, so
+/// the slots stay taken until the generation ends rather than only until
+/// the response head is sent.
+#[derive(Clone, Default)]
+struct HeldPermits(Vec<Arc<dyn Send + Sync>>);
+
+impl HeldPermits {
+    fn hold(request: &mut Request, permit: Arc<dyn Send + Sync>) {
+        request
+            .extensions_mut()
+            .get_or_insert_default::<HeldPermits>()
+            .0
+            .push(permit);
+    }
+}
+
+/// Marks a response as one completed generation, whose latency is a fair
+/// sample for the adaptive concurrency limit.
+#[derive(Clone, Copy)]
+struct SingleGeneration;
+
+#[derive(Serialize)]
+struct TwoPhaseResponse {
+    fast: ProcessResponse,
+    deep_handle: String,
+}
+
+impl ProcessResponse {
+    fn new(response: Response, encoding: OutputEncoding) -> Self {
+        let content = match encoding {
+            OutputEncoding::Utf8 => response.content,
+            OutputEncoding::Base64 => {
+                base64::engine::general_purpose::STANDARD.encode(response.content)
+            }
+        };
+
+        Self {
+            intent: format!("{:?}", response.intent),
+            system: response.system,
+            content,
+            truncated: response.truncated,
+            raw_content: response.raw_content,
+            action_items: response.action_items,
+            warnings: response.warnings,
+            effective_config: response.effective_config,
+            context_used: response.context_used,
+            model: response.model,
+            usage: response.usage,
+            token_budget_remaining: response.token_budget_remaining,
+            summary: response.summary,
+            grounded: response.grounded,
+            answer_confidence: response.answer_confidence,
+            reasoning_trace: response.reasoning_trace,
+            latency_ms: response.latency_ms,
+            encoding,
+            routing: response.routing,
+            suggestions: response.suggestions,
+            request_id: response.request_id,
+            input_hash: response.input_hash,
+            signature: response.signature,
+            original_content: response.original_content,
+            language: response.language,
+            seed: response.seed,
+            load: None,
+        }
+    }
+
+    fn with_backpressure(mut self, backpressure: Option<Backpressure>) -> Self {
+        self.load = backpressure.map(|b| b.load);
+        self
+    }
 }
 
 async fn process_directive(
-    brain: axum::extract::State<Arc<Brain>>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ProcessQuery>,
+    backpressure: Option<Extension<Backpressure>>,
+    permits: Option<Extension<HeldPermits>>,
+    axum::extract::Json(payload): axum::extract::Json<ProcessRequest>,
+) -> Result<axum::response::Response, ProcessFailure> {
+    let backpressure = backpressure.map(|Extension(b)| b);
+    match query.mode {
+        ProcessMode::Single => {
+            let response = process_logged(&state, &payload, None).await?;
+            let mut response = axum::Json(
+                ProcessResponse::new(response, payload.encoding).with_backpressure(backpressure),
+            )
+            .into_response();
+            response.extensions_mut().insert(SingleGeneration);
+            Ok(response)
+        }
+        ProcessMode::TwoPhase => {
+            let fast = process_logged(&state, &payload, Some(Intent::QuickAction)).await?;
+            let deep_handle = state.deep_jobs.start().await;
+
+            let background = state.clone();
+            let handle = deep_handle.clone();
+            let deep_payload = payload.clone();
+            tokio::spawn(async move {
+                let _permits = permits;
+                let job = match process_logged(&background, &deep_payload, Some(Intent::Strategy))
+                    .await
+                {
+                    Ok(deep) => {
+                        DeepJob::Done(Box::new(ProcessResponse::new(deep, deep_payload.encoding)))
+                    }
+                    Err(failure) => DeepJob::Failed(failure),
+                };
+                background.deep_jobs.finish(&handle, job).await;
+            });
+
+            Ok(axum::Json(TwoPhaseResponse {
+                fast: ProcessResponse::new(fast, payload.encoding).with_backpressure(backpressure),
+                deep_handle,
+            })
+            .into_response())
+        }
+        ProcessMode::DryRun => {
+            let (input, _) = directive_parts(&payload)?;
+            let report = state
+                .brain
+                .dry_run(&input, &payload.options(None))
+                .await
+                .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            Ok(axum::Json(report).into_response())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RegenerateRequest {
+    request_id: String,
+}
+
+/// `POST /process/regenerate`: answers an earlier `/process` request again
+/// with the next seed, instructing the model to differ from the answer it
+/// gave. 404 once the original is unknown or expired.
+async fn regenerate(
+    State(state): State<Arc<AppState>>,
+    backpressure: Option<Extension<Backpressure>>,
+    axum::extract::Json(request): axum::extract::Json<RegenerateRequest>,
+) -> Result<axum::response::Response, ProcessFailure> {
+    let Some(answered) = state.answered.get(&request.request_id).await else {
+        let unknown = (
+            StatusCode::NOT_FOUND,
+            "unknown or expired request_id".to_string(),
+        );
+        return Err(unknown.into());
+    };
+    let payload = ProcessRequest {
+        seed: Some(answered.seed.wrapping_add(1)),
+        previous_answer: Some(answered.content),
+        ..answered.payload
+    };
+
+    let response = process_logged(&state, &payload, None).await?;
+    Ok(axum::Json(
+        ProcessResponse::new(response, payload.encoding)
+            .with_backpressure(backpressure.map(|Extension(b)| b)),
+    )
+    .into_response())
+}
+
+/// Largest batch accepted by `POST /process/batch`.
+const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    requests: Vec<ProcessRequest>,
+    /// Process identical requests once and fan the result out.
+    #[serde(default)]
+    dedup: bool,
+}
+
+/// One batch entry's outcome, in the position of its request.
+#[derive(Serialize)]
+struct BatchItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<ProcessResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// `POST /process/batch`: processes each request in order. A failing entry
+/// reports its error in place without failing the batch.
+async fn process_batch(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Json(batch): axum::extract::Json<BatchRequest>,
+) -> Result<axum::Json<Vec<BatchItem>>, (StatusCode, String)> {
+    if batch.requests.len() > MAX_BATCH_SIZE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "batch of {} exceeds {} requests",
+                batch.requests.len(),
+                MAX_BATCH_SIZE
+            ),
+        ));
+    }
+
+    let mut done: HashMap<String, Result<Response, String>> = HashMap::new();
+    let mut items = Vec::with_capacity(batch.requests.len());
+    for payload in &batch.requests {
+        let key = payload.dedup_key();
+        let result = match done.get(&key).filter(|_| batch.dedup) {
+            Some(result) => result.clone(),
+            None => {
+                let result = process_logged(&state, payload, None)
+                    .await
+                    .map_err(|failure| failure.error.message);
+                if batch.dedup {
+                    done.insert(key, result.clone());
+                }
+                result
+            }
+        };
+
+        items.push(match result {
+            Ok(response) => BatchItem {
+                response: Some(ProcessResponse::new(response, payload.encoding)),
+                error: None,
+            },
+            Err(e) => BatchItem {
+                response: None,
+                error: Some(e),
+            },
+        });
+    }
+
+    Ok(axum::Json(items))
+}
+
+/// `GET /process/deep/{handle}`: 202 while the deep answer is pending,
+/// then the deep `ProcessResponse` once (the result is consumed).
+async fn poll_deep(
+    State(state): State<Arc<AppState>>,
+    Path(handle): Path<String>,
+) -> axum::response::Response {
+    match state.deep_jobs.poll(&handle).await {
+        None => (StatusCode::NOT_FOUND, "unknown or expired deep handle").into_response(),
+        Some(DeepJob::Pending) => (
+            StatusCode::ACCEPTED,
+            axum::Json(serde_json::json!({ "status": "pending" })),
+        )
+            .into_response(),
+        Some(DeepJob::Done(response)) => axum::Json(*response).into_response(),
+        Some(DeepJob::Failed(failure)) => failure.into_response(),
+    }
+}
+
+async fn process_logged(
+    state: &AppState,
+    payload: &ProcessRequest,
+    forced: Option<Intent>,
+) -> Result<Response, ProcessFailure> {
+    let start = std::time::Instant::now();
+    let result = run_directive(&state.brain, payload, forced).await;
+    log_request(&state.config, &payload.input, &result, start.elapsed());
+    if let Ok(response) = &result {
+        state.answered.remember(payload, response).await;
+    }
+    #[cfg(feature = "statsd")]
+    if let Some(statsd) = &state.statsd {
+        push_metrics(statsd, &result, start.elapsed());
+    }
+    result
+}
+
+/// Pushes per-request counters and timers to StatsD.
+#[cfg(feature = "statsd")]
+fn push_metrics(
+    statsd: &aegnt_unltd::statsd::StatsdClient,
+    result: &Result<Response, ProcessFailure>,
+    elapsed: Duration,
+) {
+    match result {
+        Ok(response) => {
+            let intent = format!("{:?}", response.routing.final_intent).to_lowercase();
+            statsd.count(&format!("requests.{}", intent), 1);
+            statsd.timing(&format!("latency.{}", intent), elapsed.as_millis() as u64);
+            if response.routing.downgraded {
+                statsd.count("downgraded", 1);
+            }
+        }
+        Err(failure) => statsd.count(&format!("errors.{}", failure.status.as_u16()), 1),
+    }
+}
+
+const NDJSON: &str = "application/x-ndjson";
+
+/// `POST /process/stream`: the same request as `/process`, answered as
+/// server-sent events (`reasoning_step_start`, `reasoning`,
+/// `reasoning_step_end`, `content`, then `done` or `error`). With
+/// `Accept: application/x-ndjson` the same frames are sent as one
+/// `{"type", "data"}` JSON object per line instead.
+///
+/// The `x-request-id` response header names the answer for
+/// `/process/resume/{request_id}`.
+async fn process_stream(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    permits: Option<Extension<HeldPermits>>,
     axum::extract::Json(payload): axum::extract::Json<ProcessRequest>,
-) -> axum::Json<ProcessResponse> {
-    let response = brain.process_directive(&payload.input).await;
-    
-    axum::Json(ProcessResponse {
-        intent: format!("{:?}", response.intent),
-        system: response.system,
-        content: response.content,
-        reasoning_trace: response.reasoning_trace,
-        latency_ms: response.latency_ms,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    let (input, context) = directive_parts(&payload)?;
+    let encoding = payload.encoding;
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let options = ProcessOptions {
+        request_id: Some(request_id.clone()),
+        ..payload.options(None)
+    };
+    state.streams.start(&request_id, encoding).await;
+    let cancel = state.cancellations.register(&request_id).await;
+    let spill = state
+        .config
+        .stream_spill_dir
+        .as_ref()
+        .map(|dir| SpillConfig {
+            memory_frames: state.config.stream_memory_frames,
+            path: dir.join(format!("{}.jsonl", request_id)),
+            max_bytes: state.config.stream_spill_max_bytes,
+        });
+
+    let (tx, rx) = mpsc::channel(64);
+    let id = request_id.clone();
+    let mut timing = payload.timing.then(TimingRecorder::start);
+    tokio::spawn(async move {
+        // Released once the generation and its relay are done
+        let _permits = permits;
+        let (brain_tx, mut brain_rx) = mpsc::channel(64);
+        // Dropping the generation on cancel also closes `brain_tx`, which
+        // ends the relay
+        let generate = async {
+            tokio::select! {
+                _ = state.brain.process_streaming(&input, context.as_ref(), &options, brain_tx) => false,
+                Ok(()) = cancel => true,
+            }
+        };
+        // Keep buffering after the client goes away so it can resume
+        let relay = async {
+            while let Some(mut event) = brain_rx.recv().await {
+                let done = matches!(event, StreamEvent::Done(_));
+                if let Some(timing) = &mut timing {
+                    event = timing.stamp(event);
+                }
+                state.streams.push(&id, event.clone()).await;
+                let _ = tx.send(event).await;
+                if let Some(timing) = timing.as_ref().filter(|_| done) {
+                    let summary = StreamEvent::Timing(timing.summary());
+                    state.streams.push(&id, summary.clone()).await;
+                    let _ = tx.send(summary).await;
+                }
+            }
+        };
+        let (cancelled, ()) = tokio::join!(generate, relay);
+        state.cancellations.remove(&id).await;
+        if cancelled {
+            state.streams.push(&id, StreamEvent::Cancelled).await;
+            let _ = tx.send(StreamEvent::Cancelled).await;
+        }
+    });
+    let id_header = [(HeaderName::from_static("x-request-id"), request_id)];
+
+    let wants_ndjson = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON));
+    if wants_ndjson {
+        let lines = encoded(rx, spill, move |event| {
+            frame_json(event, encoding).to_string() + "\n"
+        })
+        .map(Ok::<_, std::convert::Infallible>);
+        return Ok((
+            id_header,
+            [(header::CONTENT_TYPE, NDJSON)],
+            Body::from_stream(lines),
+        )
+            .into_response());
+    }
+
+    let frames = encoded(rx, spill, move |event| sse_frame(event, encoding)).map(|frame| {
+        Ok::<_, std::convert::Infallible>(Event::default().event(frame.event).data(frame.data))
+    });
+
+    Ok((id_header, Sse::new(frames)).into_response())
+}
+
+/// Encodes stream frames for the wire. With `spill`, frames the client
+/// hasn't read yet overflow to disk instead of stalling the stream.
+fn encoded<T>(
+    mut events: mpsc::Receiver<StreamEvent>,
+    spill: Option<SpillConfig>,
+    encode: impl Fn(StreamEvent) -> T + Send + 'static,
+) -> ReceiverStream<T>
+where
+    T: Serialize + serde::de::DeserializeOwned + Send + 'static,
+{
+    let (tx, frames) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            if tx.send(encode(event)).await.is_err() {
+                return;
+            }
+        }
+    });
+    ReceiverStream::new(match spill {
+        Some(config) => spill::relay(frames, config),
+        None => frames,
     })
 }
 
+/// An SSE frame, in a form that can spill to disk.
+#[derive(Serialize, Deserialize)]
+struct SseFrame {
+    event: String,
+    data: String,
+}
+
+fn sse_frame(event: StreamEvent, encoding: OutputEncoding) -> SseFrame {
+    let name = event.name();
+    let data = match event {
+        StreamEvent::Done(response) => {
+            serde_json::to_string(&ProcessResponse::new(*response, encoding))
+        }
+        StreamEvent::ReasoningStepStart { label } | StreamEvent::ReasoningStepEnd { label } => {
+            serde_json::to_string(&serde_json::json!({ "label": label }))
+        }
+        StreamEvent::Status(text)
+        | StreamEvent::Reasoning(text)
+        | StreamEvent::Content(text)
+        | StreamEvent::Error(text) => Ok(text),
+        StreamEvent::Cancelled => Ok("cancelled".to_string()),
+        StreamEvent::TimedContent { text, elapsed_ms } => {
+            serde_json::to_string(&serde_json::json!({ "text": text, "elapsed_ms": elapsed_ms }))
+        }
+        StreamEvent::Timing(timing) => serde_json::to_string(&timing),
+    };
+    match data {
+        Ok(data) => SseFrame {
+            event: name.to_string(),
+            data,
+        },
+        Err(e) => SseFrame {
+            event: "error".to_string(),
+            data: e.to_string(),
+        },
+    }
+}
+
+/// A stream frame as a `{"type", "data"}` object, with the final response
+/// rendered in the requested encoding.
+fn frame_json(event: StreamEvent, encoding: OutputEncoding) -> serde_json::Value {
+    let frame = match event {
+        StreamEvent::Done(response) => Ok(serde_json::json!({
+            "type": "done",
+            "data": ProcessResponse::new(*response, encoding),
+        })),
+        event => serde_json::to_value(&event),
+    };
+    frame.unwrap_or_else(|e| serde_json::json!({ "type": "error", "data": e.to_string() }))
+}
+
+/// `GET /process/resume/{request_id}`: recovers a `/process/stream` answer
+/// after a dropped connection. 200 with the response (and its `timing`
+/// summary, when one was asked for) once it's finished, 202 with the latest
+/// frames while it's still generating, `skipped` counting older frames no
+/// longer held.
+async fn resume_stream(
+    State(state): State<Arc<AppState>>,
+    Path(request_id): Path<String>,
+) -> axum::response::Response {
+    let Some(stream) = state.streams.get(&request_id).await else {
+        return (StatusCode::NOT_FOUND, "unknown or expired request_id").into_response();
+    };
+
+    // A finished stream holds its final frame first
+    match stream.frames.front() {
+        Some(StreamEvent::Done(response)) => {
+            let mut body =
+                serde_json::json!(ProcessResponse::new((**response).clone(), stream.encoding));
+            if let Some(StreamEvent::Timing(timing)) = stream.frames.get(1) {
+                body["timing"] = serde_json::json!(timing);
+            }
+            axum::Json(body).into_response()
+        }
+        Some(StreamEvent::Error(e)) => (StatusCode::BAD_REQUEST, e.clone()).into_response(),
+        Some(StreamEvent::Cancelled) => {
+            (StatusCode::GONE, "generation was cancelled").into_response()
+        }
+        _ => {
+            let frames: Vec<serde_json::Value> = stream
+                .frames
+                .into_iter()
+                .map(|event| frame_json(event, stream.encoding))
+                .collect();
+            (
+                StatusCode::ACCEPTED,
+                axum::Json(serde_json::json!({
+                    "status": "streaming",
+                    "frames": frames,
+                    "skipped": stream.skipped,
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// `POST /process/cancel/{request_id}`: stops a `/process/stream`
+/// generation, whose stream then ends with a `cancelled` frame. 202 when
+/// cancelled, 404 when nothing with that id is generating.
+async fn cancel_stream(
+    State(state): State<Arc<AppState>>,
+    Path(request_id): Path<String>,
+) -> StatusCode {
+    if state.cancellations.cancel(&request_id).await {
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Resolves the directive text and optional structured context.
+fn directive_parts(
+    payload: &ProcessRequest,
+) -> Result<(String, Option<serde_json::Value>), (StatusCode, String)> {
+    match payload.input_type {
+        InputType::Text => Ok((payload.input.clone(), None)),
+        InputType::Json => {
+            let path = payload.directive_path.as_deref().ok_or((
+                StatusCode::BAD_REQUEST,
+                "directive_path is required when input_type is json".to_string(),
+            ))?;
+            let (directive, doc) = structured::extract_directive(&payload.input, path)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            Ok((directive, Some(doc)))
+        }
+    }
+}
+
+/// A failed directive, answered with its diagnostic as the JSON body.
+#[derive(Debug, Clone)]
+struct ProcessFailure {
+    status: StatusCode,
+    error: ProcessError,
+}
+
+impl From<ProcessError> for ProcessFailure {
+    fn from(error: ProcessError) -> Self {
+        let status = match error.category {
+            ErrorCategory::InvalidRequest | ErrorCategory::PromptTooLarge => {
+                StatusCode::BAD_REQUEST
+            }
+            ErrorCategory::BackendUnavailable => StatusCode::BAD_GATEWAY,
+            ErrorCategory::BackendTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCategory::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self { status, error }
+    }
+}
+
+/// Rejections raised before the brain sees the request.
+impl From<(StatusCode, String)> for ProcessFailure {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        let error = ProcessError::new(Phase::Request, ErrorCategory::InvalidRequest, message);
+        Self { status, error }
+    }
+}
+
+impl From<ProcessFailure> for (StatusCode, String) {
+    fn from(failure: ProcessFailure) -> Self {
+        (failure.status, failure.error.message)
+    }
+}
+
+impl IntoResponse for ProcessFailure {
+    fn into_response(self) -> axum::response::Response {
+        (self.status, axum::Json(self.error)).into_response()
+    }
+}
+
+async fn run_directive(
+    brain: &Brain,
+    payload: &ProcessRequest,
+    forced: Option<Intent>,
+) -> Result<Response, ProcessFailure> {
+    let (input, context) = directive_parts(payload)?;
+
+    Ok(brain
+        .process(&input, context.as_ref(), &payload.options(forced))
+        .await?)
+}
+
+/// Whether a request gets fully logged: errors and slow queries always,
+/// other requests at `log_sample_rate`.
+fn sampled(
+    config: &ServerConfig,
+    result: &Result<Response, ProcessFailure>,
+    elapsed_ms: u64,
+) -> bool {
+    result.is_err()
+        || elapsed_ms >= config.slow_query_ms
+        || rand::random::<f64>() < config.log_sample_rate
+}
+
+/// Logs a processed request, sampling successful fast ones by
+/// `log_sample_rate`. Errors and slow queries bypass sampling.
+fn log_request(
+    config: &ServerConfig,
+    input: &str,
+    result: &Result<Response, ProcessFailure>,
+    elapsed: Duration,
+) {
+    let elapsed_ms = elapsed.as_millis() as u64;
+    if !sampled(config, result, elapsed_ms) {
+        return;
+    }
+    match result {
+        Err(failure) => {
+            eprintln!(
+                "[process] error status={} phase={:?} latency_ms={} error={}",
+                failure.status, failure.error.phase, elapsed_ms, failure.error
+            );
+        }
+        Ok(response) => {
+            let slow = elapsed_ms >= config.slow_query_ms;
+            println!(
+                "[process] intent={:?} system={} latency_ms={}{} input_len={}",
+                response.intent,
+                response.system,
+                elapsed_ms,
+                if slow { " slow=true" } else { "" },
+                input.len()
+            );
+            if let Some(trace) = &response.internal_trace {
+                println!("[process] full_trace={:?}", trace);
+            }
+        }
+    }
+}
+
+async fn slow_start(
+    State(ramp): State<Arc<SlowStart>>,
+    mut request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let permit = Arc::new(ramp.acquire().await);
+    HeldPermits::hold(&mut request, permit.clone());
+    next.run(request).await
+}
+
+async fn adaptive_limit(
+    State(limiter): State<Arc<AdaptiveLimit>>,
+    mut request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let permit = Arc::new(limiter.acquire().await);
+    HeldPermits::hold(&mut request, permit.clone());
+    let response = next.run(request).await;
+    // Streams, batches and failures say little about generation latency
+    if response.status().is_success() && response.extensions().get::<SingleGeneration>().is_some() {
+        permit.completed();
+    }
+    response
+}
+
+/// `GET /analytics/confidence`: classifier confidence histograms per
+/// intent, overall and per period, for spotting input drift.
+async fn confidence_analytics(
+    State(state): State<Arc<AppState>>,
+) -> axum::Json<aegnt_unltd::confidence::ConfidenceReport> {
+    axum::Json(state.brain.confidence_report())
+}
+
+async fn info(State(state): State<Arc<AppState>>) -> axum::Json<serde_json::Value> {
+    let config = state.brain.config();
+    axum::Json(serde_json::json!({
+        "fast_model": config.fast_model,
+        "slow_model": config.slow_model,
+        "knowledge": state.brain.knowledge_status().await,
+    }))
+}
+
+/// Feature manifest so clients can adapt to what this deployment supports.
+async fn capabilities(State(state): State<Arc<AppState>>) -> axum::Json<serde_json::Value> {
+    let brain = state.brain.config();
+    let server = &state.config;
+
+    let mut models = vec![brain.fast_model.clone(), brain.slow_model.clone()];
+    let mut pool: Vec<String> = brain.fast_model_pool.keys().cloned().collect();
+    pool.sort();
+    models.extend(pool);
+    models.extend(brain.translation_model.clone());
+    if brain.rerank {
+        models.push(brain.rerank_model.clone());
+    }
+    models.dedup();
+
+    axum::Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "intents": Intent::ALL,
+        "models": models,
+        "personas": state.brain.personas().await,
+        "features": {
+            "streaming": true,
+            "stream_formats": ["sse", NDJSON],
+            "stream_granularity": brain.stream_granularity,
+            "tools": false,
+            "two_phase": true,
+            "batch": true,
+            "dry_run": true,
+            "structured_input": true,
+            "encodings": [OutputEncoding::Utf8, OutputEncoding::Base64],
+            "translation": brain.translation_model.is_some(),
+            "signing": brain.signing_key.is_some(),
+            "fast_self_check": brain.fast_self_check,
+            "control_prefix": brain.control_prefix,
+            "sub_brains": brain
+                .sub_brains
+                .keys()
+                .chain(brain.sub_brain_regions.keys())
+                .collect::<std::collections::HashSet<_>>(),
+            "region": brain.region,
+            "webhooks": brain.webhooks.keys().collect::<Vec<_>>(),
+            "rate_limit_per_minute": server.rate_limit_per_minute,
+            "max_queue_depth": server.max_queue_depth,
+            "backpressure_load": server.max_queue_depth.map(|_| server.backpressure_load),
+        },
+    }))
+}
+
+async fn reindex_knowledge(State(state): State<Arc<AppState>>) -> StatusCode {
+    match state.brain.reindex_knowledge().await {
+        Some(_) => StatusCode::ACCEPTED,
+        None => StatusCode::CONFLICT,
+    }
+}
+
+#[derive(Deserialize)]
+struct CompareRequest {
+    proposed_config: ClassifierConfig,
+    inputs: Vec<String>,
+}
+
+/// `POST /classifier/compare`: how each input routes under the current
+/// and the proposed classifier config.
+async fn compare_classifier(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Json(compare): axum::extract::Json<CompareRequest>,
+) -> Result<axum::Json<serde_json::Value>, (StatusCode, String)> {
+    if compare.inputs.len() > MAX_BATCH_SIZE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{} inputs exceeds {} per comparison",
+                compare.inputs.len(),
+                MAX_BATCH_SIZE
+            ),
+        ));
+    }
+
+    let diffs = state
+        .brain
+        .compare_classifier(&compare.proposed_config, &compare.inputs)
+        .await;
+    Ok(axum::Json(serde_json::json!({
+        "current_config": state.brain.classifier_config(),
+        "changed": diffs.iter().filter(|d| d.changed).count(),
+        "results": diffs,
+    })))
+}
+
+#[derive(Deserialize)]
+struct FeedbackRequest {
+    request_id: String,
+    intent: Intent,
+}
+
+/// `POST /classifier/feedback`: the intent a request should have been
+/// classified as, appended to the classification feature log.
+async fn classifier_feedback(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Json(feedback): axum::extract::Json<FeedbackRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if state.brain.config().classification_feature_log.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "classification feature logging is off".to_string(),
+        ));
+    }
+    state
+        .brain
+        .record_correction(&feedback.request_id, feedback.intent)
+        .await
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+struct RateLimiter {
+    store: Box<dyn RateLimitStore>,
+    limit: u64,
+}
+
+impl RateLimiter {
+    fn from_config(config: &ServerConfig) -> Option<Self> {
+        let limit = config.rate_limit_per_minute?;
+
+        let store: Box<dyn RateLimitStore> = match &config.redis_url {
+            #[cfg(feature = "redis")]
+            Some(url) => Box::new(
+                aegnt_unltd::rate_limit::RedisStore::new(url, "aegnt:ratelimit")
+                    .expect("invalid AEGNT_REDIS_URL"),
+            ),
+            #[cfg(not(feature = "redis"))]
+            Some(_) => {
+                eprintln!("AEGNT_REDIS_URL set but built without the `redis` feature; limiting per instance");
+                Box::new(InMemoryStore::default())
+            }
+            None => Box::new(InMemoryStore::default()),
+        };
+
+        Some(Self { store, limit })
+    }
+}
+
+async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let key = addr.ip().to_string();
+    match limiter.store.hit(&key, Duration::from_secs(60)).await {
+        Ok(count) if count > limiter.limit => {
+            return (StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded").into_response();
+        }
+        Ok(_) => {}
+        // Fail open: a store outage shouldn't take the brain down with it.
+        Err(e) => eprintln!("[rate_limit] store error: {}", e),
+    }
+    next.run(request).await
+}
+
+/// Admission-time queue load, attached to requests admitted while the
+/// queue is above `backpressure_load` so clients can slow down before
+/// they start getting 503s.
+#[derive(Debug, Clone, Copy)]
+struct Backpressure {
+    load: f32,
+    retry_after_secs: u64,
+}
+
+impl Backpressure {
+    fn measure(queue: &BoundedQueue, threshold: f32) -> Option<Self> {
+        let load = queue.load();
+        (load >= threshold).then(|| Self {
+            load,
+            // Roughly how long a request arriving now would wait.
+            retry_after_secs: (queue.max_wait().as_secs_f32() * load).ceil().max(1.0) as u64,
+        })
+    }
+
+    fn apply(self, response: &mut axum::response::Response) {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, self.retry_after_secs.into());
+    }
+}
+
+/// Queue priority from the `X-Priority` header; normal when absent.
+fn priority(headers: &HeaderMap) -> Result<Priority, String> {
+    match headers.get("x-priority") {
+        None => Ok(Priority::Normal),
+        Some(value) => value
+            .to_str()
+            .map_err(|_| "invalid priority header".to_string())?
+            .parse(),
+    }
+}
+
+async fn queue_slot(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let priority = match priority(request.headers()) {
+        Ok(priority) => priority,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let Some(queue) = state.queue.as_ref() else {
+        return next.run(request).await;
+    };
+
+    match queue.acquire(priority).await {
+        Ok(permit) => {
+            let permit = Arc::new(permit);
+            HeldPermits::hold(&mut request, permit.clone());
+            let backpressure = Backpressure::measure(queue, state.config.backpressure_load);
+            if let Some(b) = backpressure {
+                request.extensions_mut().insert(b);
+            }
+            let mut response = next.run(request).await;
+            if let Some(b) = backpressure {
+                b.apply(&mut response);
+            }
+            response
+        }
+        Err(e) => {
+            let mut response = (StatusCode::SERVICE_UNAVAILABLE, e.to_string()).into_response();
+            if let Some(b) = Backpressure::measure(queue, 0.0) {
+                b.apply(&mut response);
+            }
+            response
+        }
+    }
+}
+
+/// Prometheus text-format metrics.
+async fn metrics(State(state): State<Arc<AppState>>) -> String {
+    let (depth, in_flight) = state
+        .queue
+        .as_ref()
+        .map_or((0, 0), |q| (q.depth(), q.in_flight()));
+
+    let mut metrics = format!(
+        "# TYPE aegnt_queue_depth gauge\naegnt_queue_depth {}\n\
+         # TYPE aegnt_model_slots_in_use gauge\naegnt_model_slots_in_use {}\n",
+        depth, in_flight
+    );
+    if let Some(limiter) = &state.adaptive {
+        metrics.push_str(&format!(
+            "# TYPE aegnt_concurrency_limit gauge\naegnt_concurrency_limit {}\n",
+            limiter.limit()
+        ));
+    }
+    metrics
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Readiness {
+    /// Still loading (the first knowledge index build hasn't finished).
+    Warming,
+    Ready,
+    /// Startup loading failed; this instance won't become ready by itself.
+    Unhealthy,
+}
+
+/// `GET /ready`: 200 once ready, otherwise 503 with `status` saying whether
+/// the instance is still warming or unhealthy.
+async fn ready(State(state): State<Arc<AppState>>) -> (StatusCode, axum::Json<serde_json::Value>) {
+    let knowledge = state.brain.knowledge_status().await;
+    let first_build = knowledge.last_indexed_at.is_none();
+
+    let (readiness, reason) = if let Some(e) = &state.startup_error {
+        (Readiness::Unhealthy, Some(e.clone()))
+    } else if first_build && knowledge.state == IndexState::Failed {
+        (Readiness::Unhealthy, knowledge.last_error.clone())
+    } else if first_build {
+        (
+            Readiness::Warming,
+            Some("building knowledge index".to_string()),
+        )
+    } else {
+        (Readiness::Ready, None)
+    };
+
+    let status = match readiness {
+        Readiness::Ready => StatusCode::OK,
+        Readiness::Warming | Readiness::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (
+        status,
+        axum::Json(serde_json::json!({ "status": readiness, "reason": reason })),
+    )
+}
+
 async fn health() -> &'static str {
     "OK"
 }
 
+impl AppState {
+    /// Builds the server's state: loads prompt templates and
+    /// classification overrides, and starts the first knowledge index build.
+    async fn new(server_config: ServerConfig, brain: Brain) -> Arc<Self> {
+        let queue = server_config.max_queue_depth.map(|depth| {
+            Arc::new(BoundedQueue::new(
+                server_config.model_slots,
+                depth,
+                Duration::from_millis(server_config.max_queue_wait_ms),
+            ))
+        });
+
+        let adaptive = server_config.adaptive_concurrency.then(|| {
+            Arc::new(AdaptiveLimit::new(
+                server_config.min_concurrency,
+                server_config.max_concurrency,
+            ))
+        });
+
+        let startup_error = match brain.load_prompt_templates().await {
+            Ok(()) => brain
+                .load_classification_overrides()
+                .await
+                .map_err(|e| format!("failed to load classification overrides: {}", e))
+                .err(),
+            Err(e) => Some(format!("failed to load prompt templates: {}", e)),
+        };
+        if let Some(e) = &startup_error {
+            eprintln!("{}", e);
+        }
+        brain.reindex_knowledge().await;
+
+        Arc::new(AppState {
+            brain,
+            queue,
+            adaptive,
+            deep_jobs: DeepJobs::default(),
+            streams: StreamBuffers::new(
+                server_config.resume_max_frames,
+                server_config.resume_max_streams,
+            ),
+            cancellations: Cancellations::default(),
+            answered: AnsweredRequests::new(server_config.regenerate_max_answers),
+            startup_error,
+            #[cfg(feature = "statsd")]
+            statsd: server_config.statsd_addr.as_deref().map(|addr| {
+                aegnt_unltd::statsd::StatsdClient::new(addr, server_config.statsd_prefix.clone())
+                    .expect("invalid AEGNT_STATSD_ADDR")
+            }),
+            config: server_config,
+        })
+    }
+}
+
+/// The server's routes, with the concurrency and rate-limit middleware
+/// `state.config` turns on.
+fn router(state: Arc<AppState>) -> Router {
+    let server_config = &state.config;
+    let mut process_routes = Router::new()
+        .route("/process", post(process_directive))
+        .route("/process/stream", post(process_stream))
+        .route("/process/batch", post(process_batch))
+        .route("/process/regenerate", post(regenerate));
+    if state.queue.is_some() {
+        process_routes =
+            process_routes.route_layer(middleware::from_fn_with_state(state.clone(), queue_slot));
+    }
+    if let Some(ms) = server_config.slow_start_ms {
+        let ramp = Arc::new(SlowStart::new(
+            Duration::from_millis(ms),
+            server_config.slow_start_initial,
+            server_config.max_concurrency,
+        ));
+        process_routes =
+            process_routes.route_layer(middleware::from_fn_with_state(ramp, slow_start));
+    }
+
+    if let Some(limiter) = state.adaptive.clone() {
+        process_routes =
+            process_routes.route_layer(middleware::from_fn_with_state(limiter, adaptive_limit));
+    }
+
+    if let Some(limiter) = RateLimiter::from_config(server_config) {
+        process_routes = process_routes.route_layer(middleware::from_fn_with_state(
+            Arc::new(limiter),
+            rate_limit,
+        ));
+    }
+
+    Router::new()
+        .route("/", get(health))
+        .route("/ready", get(ready))
+        .route("/info", get(info))
+        .route("/capabilities", get(capabilities))
+        .route("/metrics", get(metrics))
+        .route("/process/deep/{handle}", get(poll_deep))
+        .route("/process/resume/{request_id}", get(resume_stream))
+        .route("/process/cancel/{request_id}", post(cancel_stream))
+        .route("/knowledge/reindex", post(reindex_knowledge))
+        .route("/classifier/compare", post(compare_classifier))
+        .route("/classifier/feedback", post(classifier_feedback))
+        .route("/analytics/confidence", get(confidence_analytics))
+        .merge(process_routes)
+        .with_state(state)
+}
+
 #[tokio::main]
 async fn main() {
-    let config = BrainConfig {
-        fast_model: "ollama:llama3".to_string(),
-        slow_model: "gemini-3.1-pro".to_string(),
-    };
-    
-    let brain = Arc::new(Brain::new(config));
-    
-    let app = Router::new()
-        .route("/", get(health))
-        .route("/process", post(process_directive))
-        .with_state(brain);
-    
+    let server_config = ServerConfig::from_env();
+
+    let config = server_config
+        .brain_config()
+        .unwrap_or_else(|e| panic!("invalid AEGNT_BRAIN_CONFIG: {}", e));
+
+    #[cfg(not(feature = "statsd"))]
+    if server_config.statsd_addr.is_some() {
+        eprintln!(
+            "AEGNT_STATSD_ADDR set but built without the `statsd` feature; not pushing metrics"
+        );
+    }
+
+    let app = router(AppState::new(server_config, Brain::new(config)).await);
+
     let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
     println!("🚀 AEGNT-UNLTD running on http://{}", addr);
-    
+
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::connect_info::MockConnectInfo;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    /// `server`'s brain settings, with no knowledge.
+    fn test_brain_config(server: &ServerConfig) -> BrainConfig {
+        let scratch = std::env::temp_dir().join(format!("aegnt-server-{}", uuid::Uuid::new_v4()));
+        BrainConfig {
+            memory_path: scratch.join("memory"),
+            knowledge_path: scratch.join("knowledge"),
+            ..server.brain_config().unwrap()
+        }
+    }
+
+    async fn test_state(server: ServerConfig) -> Arc<AppState> {
+        let brain = Brain::new(test_brain_config(&server));
+        AppState::new(server, brain).await
+    }
+
+    fn test_router(state: Arc<AppState>) -> Router {
+        router(state).layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+    }
+
+    async fn test_app(server: ServerConfig) -> Router {
+        test_router(test_state(server).await)
+    }
+
+    fn post(uri: &str, body: Value) -> Request {
+        axum::http::Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request {
+        axum::http::Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    /// A `/process/stream` request answered as ndjson.
+    fn ndjson_stream(body: Value) -> Request {
+        let mut request = post("/process/stream", body);
+        let accept = header::HeaderValue::from_static(NDJSON);
+        request.headers_mut().insert(header::ACCEPT, accept);
+        request
+    }
+
+    /// The frames of a whole ndjson stream body.
+    fn ndjson_frames(body: &Value) -> Vec<Value> {
+        let text = body.as_str().expect("an ndjson body isn't one JSON value");
+        text.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// Sends `request` and reads the whole body: JSON when it parses,
+    /// otherwise the text as a JSON string.
+    async fn send(app: &Router, request: Request) -> (StatusCode, HeaderMap, Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+        (status, headers, body)
+    }
+
+    #[test]
+    fn test_brain_config_file_overrides_defaults() {
+        let path = std::env::temp_dir().join(format!("aegnt-brain-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"{"slow_model": "claude-deep", "knowledge_top_k": 5, "signing_key": "from-file"}"#,
+        )
+        .unwrap();
+        let server = ServerConfig {
+            brain_config: Some(path.clone()),
+            signing_key: None,
+            ..ServerConfig::from_env()
+        };
+
+        let config = server.brain_config().unwrap();
+        assert_eq!(config.slow_model, "claude-deep");
+        assert_eq!(config.knowledge_top_k, Some(5));
+        assert_eq!(config.signing_key.as_deref(), Some("from-file"));
+        // Fields the file leaves out keep the server's defaults
+        assert_eq!(config.fast_model, "ollama:llama3");
+        assert_eq!(config.knowledge_path, PathBuf::from("knowledge"));
+
+        let server = ServerConfig {
+            signing_key: Some("from-env".to_string()),
+            ..server
+        };
+        let config = server.brain_config().unwrap();
+        assert_eq!(config.signing_key.as_deref(), Some("from-env"));
+
+        std::fs::write(&path, r#"{"knowledge_top_k": "five"}"#).unwrap();
+        assert!(server.brain_config().is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sampling_keeps_errors_and_slow_queries() {
+        let config = ServerConfig {
+            log_sample_rate: 0.0,
+            slow_query_ms: 1000,
+            ..ServerConfig::from_env()
+        };
+        let answered = Ok(Response::new(
+            Intent::QuickAction,
+            "cortex",
+            "done".to_string(),
+        ));
+        let failed = Err(ProcessFailure::from((
+            StatusCode::BAD_GATEWAY,
+            "backend down".to_string(),
+        )));
+
+        assert!(!sampled(&config, &answered, 10));
+        assert!(sampled(&config, &answered, 1000));
+        assert!(sampled(&config, &failed, 10));
+
+        let config = ServerConfig {
+            log_sample_rate: 1.0,
+            ..config
+        };
+        assert!(sampled(&config, &answered, 10));
+    }
+
+    #[tokio::test]
+    async fn test_base64_encoding_is_applied_and_reported() {
+        let app = test_app(ServerConfig::from_env()).await;
+        let request = json!({ "input": "rename the config file", "seed": 7 });
+        let (status, _, plain) = send(&app, post("/process", request.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(plain["encoding"], "utf8");
+
+        let mut encoded = request;
+        encoded["encoding"] = json!("base64");
+        let (status, _, body) = send(&app, post("/process", encoded)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["encoding"], "base64");
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(body["content"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(String::from_utf8(decoded).unwrap(), plain["content"]);
+    }
+
+    #[tokio::test]
+    async fn test_two_phase_returns_fast_answer_and_polls_deep_one() {
+        let app = test_app(ServerConfig::from_env()).await;
+        let request = json!({ "input": "plan the pricing strategy for next year" });
+        let (status, _, body) = send(&app, post("/process?mode=two_phase", request)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["fast"]["intent"], "QuickAction");
+        let handle = body["deep_handle"].as_str().unwrap().to_string();
+
+        let uri = format!("/process/deep/{}", handle);
+        let mut polls = 0;
+        let deep = loop {
+            let (status, _, body) = send(&app, get(&uri)).await;
+            if status != StatusCode::ACCEPTED {
+                assert_eq!(status, StatusCode::OK);
+                break body;
+            }
+            assert_eq!(body["status"], "pending");
+            polls += 1;
+            assert!(polls < 500, "deep answer never arrived");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(deep["intent"], "Strategy");
+
+        // The deep result is handed out once
+        let (status, _, _) = send(&app, get(&uri)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_batch_dedup_answers_repeated_inputs_once() {
+        let app = test_app(ServerConfig::from_env()).await;
+        let requests = json!([
+            { "input": "rename the config file" },
+            { "input": "delete the temp folder" },
+            { "input": "rename the config file" },
+        ]);
+        let request_ids = |body: &Value| -> Vec<String> {
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["response"]["request_id"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let batch = json!({ "requests": requests, "dedup": true });
+        let (status, _, body) = send(&app, post("/process/batch", batch)).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids = request_ids(&body);
+        // One answer, fanned out to both positions of the repeated input
+        assert_eq!(ids[0], ids[2]);
+        assert_ne!(ids[0], ids[1]);
+
+        let batch = json!({ "requests": requests, "dedup": false });
+        let (_, _, body) = send(&app, post("/process/batch", batch)).await;
+        let ids = request_ids(&body);
+        assert_ne!(ids[0], ids[2]);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_stream_sends_one_frame_per_line() {
+        let app = test_app(ServerConfig::from_env()).await;
+        let request = ndjson_stream(json!({ "input": "plan the pricing strategy for next year" }));
+        let (status, headers, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::CONTENT_TYPE], NDJSON);
+        assert!(headers.contains_key("x-request-id"));
+
+        let frames = ndjson_frames(&body);
+        assert!(frames.iter().all(|f| f["type"].is_string()));
+        assert!(frames.iter().any(|f| f["type"] == "reasoning"));
+        let last = frames.last().unwrap();
+        assert_eq!(last["type"], "done");
+
+        let content: String = frames
+            .iter()
+            .filter(|f| f["type"] == "content")
+            .map(|f| f["data"].as_str().unwrap())
+            .collect();
+        assert_eq!(content, last["data"]["content"]);
+    }
+
+    #[tokio::test]
+    async fn test_resume_returns_finished_and_partial_streams() {
+        let state = test_state(ServerConfig::from_env()).await;
+        let app = test_router(state.clone());
+        let request = ndjson_stream(json!({ "input": "plan the pricing strategy for next year" }));
+        let (_, headers, body) = send(&app, request).await;
+        let request_id = headers["x-request-id"].to_str().unwrap();
+        let done = ndjson_frames(&body).pop().unwrap();
+
+        let (status, _, resumed) =
+            send(&app, get(&format!("/process/resume/{}", request_id))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(resumed["content"], done["data"]["content"]);
+
+        // A stream still generating resumes to the frames so far
+        state.streams.start("partial", OutputEncoding::Utf8).await;
+        let chunk = StreamEvent::Content("Ship ".to_string());
+        state.streams.push("partial", chunk).await;
+        let (status, _, partial) = send(&app, get("/process/resume/partial")).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(partial["status"], "streaming");
+        assert_eq!(
+            partial["frames"],
+            json!([{ "type": "content", "data": "Ship " }])
+        );
+
+        let (status, _, _) = send(&app, get("/process/resume/unknown")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_resume_keeps_the_timing_summary() {
+        let app = test_app(ServerConfig::from_env()).await;
+        let body = json!({ "input": "plan the pricing strategy for next year", "timing": true });
+        let (_, headers, body) = send(&app, ndjson_stream(body)).await;
+        let request_id = headers["x-request-id"].to_str().unwrap();
+        let frames = ndjson_frames(&body);
+        let types: Vec<&str> = frames
+            .iter()
+            .rev()
+            .take(2)
+            .map(|f| f["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(types, ["timing", "done"]);
+        let timing = &frames.last().unwrap()["data"];
+
+        let (status, _, resumed) =
+            send(&app, get(&format!("/process/resume/{}", request_id))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            resumed["content"],
+            frames[frames.len() - 2]["data"]["content"]
+        );
+        assert_eq!(resumed["timing"]["chunks"], timing["chunks"]);
+        assert!(resumed["timing"]["time_to_first_token_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_stream_buffers_are_bounded() {
+        let buffers = StreamBuffers::new(2, 2);
+        buffers.start("a", OutputEncoding::Utf8).await;
+        for chunk in ["one", "two", "three"] {
+            let event = StreamEvent::Content(chunk.to_string());
+            buffers.push("a", event).await;
+        }
+        let stream = buffers.get("a").await.unwrap();
+        assert_eq!(stream.frames.len(), 2);
+        assert_eq!(stream.skipped, 1);
+
+        // A finished stream keeps only its final frame
+        buffers.push("a", StreamEvent::Cancelled).await;
+        let stream = buffers.get("a").await.unwrap();
+        assert!(matches!(
+            stream.frames.iter().collect::<Vec<_>>()[..],
+            [StreamEvent::Cancelled]
+        ));
+        assert_eq!(stream.skipped, 3);
+
+        buffers.start("b", OutputEncoding::Utf8).await;
+        buffers.start("c", OutputEncoding::Utf8).await;
+        assert!(buffers.get("a").await.is_none());
+        assert!(buffers.get("b").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_backpressure_hints_under_load() {
+        let server = ServerConfig {
+            model_slots: 1,
+            max_queue_depth: Some(1),
+            max_queue_wait_ms: 100,
+            ..ServerConfig::from_env()
+        };
+        let state = test_state(server.clone()).await;
+        let app = test_router(state.clone());
+        let request = json!({ "input": "rename the config file" });
+
+        let (status, headers, body) = send(&app, post("/process", request.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!headers.contains_key(header::RETRY_AFTER));
+        assert!(body["load"].is_null());
+
+        // Every slot taken for longer than a request may wait
+        let queue = state.queue.as_ref().unwrap();
+        let held = queue.acquire(Priority::Normal).await.unwrap();
+        let (status, headers, _) = send(&app, post("/process", request.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(headers[header::RETRY_AFTER], "1");
+        drop(held);
+
+        // Admitted at or above the threshold: answered, with a hint
+        let app = test_app(ServerConfig {
+            backpressure_load: 0.0,
+            ..server
+        })
+        .await;
+        let (status, headers, body) = send(&app, post("/process", request)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(headers.contains_key(header::RETRY_AFTER));
+        assert_eq!(body["load"], 0.0);
+    }
+
+    /// A classifier that takes far longer than any test runs.
+    struct Stalled;
+
+    #[async_trait::async_trait]
+    impl aegnt_unltd::classifier::Classifier for Stalled {
+        async fn classify(&self, _input: &str) -> (Intent, String, f32) {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            (Intent::Strategy, "stalled".to_string(), 1.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_a_stream_with_a_cancelled_frame() {
+        let server = ServerConfig::from_env();
+        let config = BrainConfig {
+            classification_timeout_ms: Some(120_000),
+            ..test_brain_config(&server)
+        };
+        let brain = Brain::new(config).with_classifier(Arc::new(Stalled));
+        let app = test_router(AppState::new(server, brain).await);
+
+        // The response head arrives while generation is still classifying
+        let request = ndjson_stream(json!({ "input": "plan the pricing strategy for next year" }));
+        let response = app.clone().oneshot(request).await.unwrap();
+        let request_id = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let cancel = format!("/process/cancel/{}", request_id);
+        let (status, _, _) = send(&app, post(&cancel, json!({}))).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = Value::String(String::from_utf8(bytes.to_vec()).unwrap());
+        let frames = ndjson_frames(&body);
+        assert_eq!(frames.last().unwrap()["type"], "cancelled");
+
+        let (status, _, _) = send(&app, post(&cancel, json!({}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let resume = format!("/process/resume/{}", request_id);
+        let (status, _, _) = send(&app, get(&resume)).await;
+        assert_eq!(status, StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_failures_answer_with_their_diagnostic() {
+        let server = ServerConfig::from_env();
+        let config = BrainConfig {
+            max_prompt_tokens: Some(1),
+            overflow_strategy: aegnt_unltd::prompt::OverflowStrategy::Error,
+            ..test_brain_config(&server)
+        };
+        let app = test_router(AppState::new(server, Brain::new(config)).await);
+
+        let request = json!({ "input": "plan the pricing strategy for next year" });
+        let (status, _, body) = send(&app, post("/process", request)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["phase"], "prompt");
+        assert_eq!(body["category"], "prompt_too_large");
+        assert_eq!(body["retryable"], false);
+        assert!(body["request_id"].is_string());
+        assert!(body["message"].is_string());
+
+        // Rejected before the brain sees it
+        let request = json!({ "input": "{}", "input_type": "json" });
+        let (status, _, body) = send(&app, post("/process", request)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["phase"], "request");
+        assert_eq!(body["category"], "invalid_request");
+        assert!(body["request_id"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_ready_is_warming_until_the_first_index_build() {
+        let app = test_app(ServerConfig::from_env()).await;
+
+        // The build task hasn't had a chance to run yet
+        let (status, _, body) = send(&app, get("/ready")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "warming");
+        assert_eq!(body["reason"], "building knowledge index");
+    }
+
+    #[tokio::test]
+    async fn test_ready_answers_ok_once_indexed() {
+        let app = test_app(ServerConfig::from_env()).await;
+
+        let mut ready = send(&app, get("/ready")).await;
+        for _ in 0..100 {
+            if ready.0 != StatusCode::SERVICE_UNAVAILABLE {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            ready = send(&app, get("/ready")).await;
+        }
+        let (status, _, body) = ready;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+        assert!(body["reason"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_ready_reports_a_failed_startup_as_unhealthy() {
+        let server = ServerConfig::from_env();
+        let missing = std::env::temp_dir().join(format!("aegnt-missing-{}", uuid::Uuid::new_v4()));
+        let config = BrainConfig {
+            prompt_templates: HashMap::from([("ollama:llama3".to_string(), missing)]),
+            ..test_brain_config(&server)
+        };
+        let app = test_router(AppState::new(server, Brain::new(config)).await);
+
+        let (status, _, body) = send(&app, get("/ready")).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unhealthy");
+        let reason = body["reason"].as_str().unwrap();
+        assert!(
+            reason.starts_with("failed to load prompt templates"),
+            "{}",
+            reason
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_advertise_what_is_configured() {
+        let app = test_app(ServerConfig::from_env()).await;
+        let (status, _, body) = send(&app, get("/capabilities")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["intents"], json!(Intent::ALL));
+        let features = &body["features"];
+        assert_eq!(features["streaming"], true);
+        assert_eq!(features["stream_formats"], json!(["sse", NDJSON]));
+        assert_eq!(features["tools"], false);
+        assert_eq!(features["two_phase"], true);
+        assert_eq!(features["batch"], true);
+        assert_eq!(features["dry_run"], true);
+        assert_eq!(features["encodings"], json!(["utf8", "base64"]));
+        assert_eq!(features["translation"], false);
+        assert_eq!(features["signing"], false);
+        assert_eq!(features["fast_self_check"], false);
+        assert!(features["rate_limit_per_minute"].is_null());
+        assert!(features["max_queue_depth"].is_null());
+        assert!(features["backpressure_load"].is_null());
+
+        // Optional features show up once they're configured
+        let server = ServerConfig {
+            rate_limit_per_minute: Some(60),
+            max_queue_depth: Some(8),
+            ..ServerConfig::from_env()
+        };
+        let config = BrainConfig {
+            translation_model: Some("ollama:translate".to_string()),
+            signing_key: Some("secret".to_string()),
+            fast_self_check: true,
+            ..test_brain_config(&server)
+        };
+        let backpressure_load = server.backpressure_load;
+        let app = test_router(AppState::new(server, Brain::new(config)).await);
+        let (_, _, body) = send(&app, get("/capabilities")).await;
+        assert!(body["models"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("ollama:translate")));
+        let features = &body["features"];
+        assert_eq!(features["translation"], true);
+        assert_eq!(features["signing"], true);
+        assert_eq!(features["fast_self_check"], true);
+        assert_eq!(features["rate_limit_per_minute"], 60);
+        assert_eq!(features["max_queue_depth"], 8);
+        assert_eq!(features["backpressure_load"], json!(backpressure_load));
+    }
 }
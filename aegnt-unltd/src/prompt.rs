@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+
+/// What to drop first when the assembled prompt exceeds `max_prompt_tokens`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowStrategy {
+    /// Drop knowledge chunks first, then history.
+    #[default]
+    TrimKnowledge,
+    /// Drop history turns first, then knowledge.
+    TrimHistory,
+    /// Refuse to assemble an over-budget prompt.
+    Error,
+}
+
+/// Shape the answer should take, asked for in the system prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Plain,
+    /// Markdown with headed sections.
+    Markdown,
+    /// Fenced code blocks, with any prose kept brief.
+    Code,
+}
+
+impl Format {
+    pub fn instruction(self) -> &'static str {
+        match self {
+            Format::Plain => "Answer in plain text, without Markdown.",
+            Format::Markdown => "Answer in Markdown, organized under headed sections.",
+            Format::Code => "Answer with fenced code blocks, keeping any prose brief.",
+        }
+    }
+}
+
+/// The pieces that make up a model prompt, in the order they are emitted.
+pub struct PromptParts<'a> {
+    pub system: &'a str,
+    pub knowledge: &'a [String],
+    pub history: &'a [String],
+    pub input: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssembledPrompt {
+    /// System prompt, knowledge and history: everything but the input.
+    pub system: String,
+    pub user: String,
+    pub text: String,
+    pub tokens: usize,
+    /// Human-readable notes on what was dropped to fit the budget.
+    pub trimmed: Vec<String>,
+}
+
+/// Rough token estimate (~4 bytes per token) used for budgeting.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Splits `text` into consecutive chunks of at most ~`max_tokens` each,
+/// cutting between words. A single word longer than the budget becomes a
+/// chunk of its own.
+pub fn chunk(text: &str, max_tokens: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let (mut start, mut end) = (0, 0);
+    for word in text.split_inclusive(char::is_whitespace) {
+        if end > start && estimate_tokens(&text[start..end + word.len()]) > max_tokens {
+            chunks.push(&text[start..end]);
+            start = end;
+        }
+        end += word.len();
+    }
+    if end > start {
+        chunks.push(&text[start..end]);
+    }
+    chunks
+}
+
+/// Assembles the prompt, trimming parts according to `strategy` until it
+/// fits within `max_tokens`. With no budget, everything is kept.
+pub fn assemble(
+    parts: &PromptParts,
+    max_tokens: Option<usize>,
+    strategy: OverflowStrategy,
+) -> Result<AssembledPrompt, String> {
+    let mut knowledge: Vec<&String> = parts.knowledge.iter().collect();
+    let mut history: Vec<&String> = parts.history.iter().collect();
+    let mut trimmed = Vec::new();
+
+    let total = |k: &[&String], h: &[&String]| {
+        estimate_tokens(parts.system)
+            + k.iter().map(|s| estimate_tokens(s)).sum::<usize>()
+            + h.iter().map(|s| estimate_tokens(s)).sum::<usize>()
+            + estimate_tokens(parts.input)
+    };
+
+    if let Some(max) = max_tokens {
+        let initial = total(&knowledge, &history);
+        if initial > max {
+            if strategy == OverflowStrategy::Error {
+                return Err(format!(
+                    "prompt of ~{} tokens exceeds max_prompt_tokens ({})",
+                    initial, max
+                ));
+            }
+
+            let order = match strategy {
+                OverflowStrategy::TrimHistory => [false, true],
+                _ => [true, false],
+            };
+            for trim_knowledge in order {
+                let mut dropped = 0;
+                while total(&knowledge, &history) > max {
+                    // Knowledge is dropped from the least relevant (last) end,
+                    // history from the oldest (first) end.
+                    let removed = if trim_knowledge {
+                        knowledge.pop().is_some()
+                    } else if history.is_empty() {
+                        false
+                    } else {
+                        history.remove(0);
+                        true
+                    };
+                    if !removed {
+                        break;
+                    }
+                    dropped += 1;
+                }
+                if dropped > 0 {
+                    let label = if trim_knowledge {
+                        "knowledge chunk"
+                    } else {
+                        "history turn"
+                    };
+                    trimmed.push(format!("dropped {} {}(s)", dropped, label));
+                }
+            }
+
+            let remaining = total(&knowledge, &history);
+            if remaining > max {
+                return Err(format!(
+                    "prompt of ~{} tokens exceeds max_prompt_tokens ({}) after trimming",
+                    remaining, max
+                ));
+            }
+        }
+    }
+
+    let system = join_sections(
+        std::iter::once(parts.system)
+            .chain(knowledge.iter().map(|s| s.as_str()))
+            .chain(history.iter().map(|s| s.as_str())),
+    );
+    let text = join_sections([system.as_str(), parts.input].into_iter());
+
+    Ok(AssembledPrompt {
+        tokens: estimate_tokens(&text),
+        system,
+        user: parts.input.to_string(),
+        text,
+        trimmed,
+    })
+}
+
+fn join_sections<'a>(sections: impl Iterator<Item = &'a str>) -> String {
+    let mut text = String::new();
+    for section in sections.filter(|s| !s.is_empty()) {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.push_str(section);
+    }
+    text
+}
+
+/// A model-specific chat template with `{{system}}` and `{{user}}`
+/// placeholders (e.g. Llama, Mistral or ChatML formatting).
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub text: String,
+}
+
+impl PromptTemplate {
+    pub fn render(&self, system: &str, user: &str) -> String {
+        self.text
+            .replace("{{system}}", system)
+            .replace("{{user}}", user)
+    }
+}
+
+impl AssembledPrompt {
+    /// Re-renders the prompt text through a model's chat template.
+    pub fn apply_template(&mut self, template: &PromptTemplate) {
+        self.text = template.render(&self.system, &self.user);
+        self.tokens = estimate_tokens(&self.text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflow_strategies() {
+        let knowledge = vec!["k".repeat(40), "k".repeat(40)];
+        let history = vec!["h".repeat(40)];
+        let parts = PromptParts {
+            system: "system",
+            knowledge: &knowledge,
+            history: &history,
+            input: "input",
+        };
+
+        let full = assemble(&parts, None, OverflowStrategy::Error).unwrap();
+        assert!(full.trimmed.is_empty());
+
+        let trimmed = assemble(&parts, Some(20), OverflowStrategy::TrimKnowledge).unwrap();
+        assert_eq!(trimmed.trimmed, vec!["dropped 2 knowledge chunk(s)"]);
+        assert!(trimmed.tokens <= 20);
+
+        let trimmed = assemble(&parts, Some(20), OverflowStrategy::TrimHistory).unwrap();
+        assert_eq!(
+            trimmed.trimmed,
+            vec!["dropped 1 history turn(s)", "dropped 1 knowledge chunk(s)"]
+        );
+
+        assert!(assemble(&parts, Some(20), OverflowStrategy::Error).is_err());
+    }
+
+    #[test]
+    fn test_chunk_between_words() {
+        let text = "alpha beta gamma delta epsilon";
+        let chunks = chunk(text, 3);
+        assert_eq!(chunks, vec!["alpha beta ", "gamma delta ", "epsilon"]);
+        assert_eq!(chunks.concat(), text);
+        assert_eq!(chunk(text, 100), vec![text]);
+    }
+
+    #[test]
+    fn test_apply_template() {
+        let parts = PromptParts {
+            system: "Be brief.",
+            knowledge: &[],
+            history: &[],
+            input: "Hi",
+        };
+        let mut prompt = assemble(&parts, None, OverflowStrategy::default()).unwrap();
+        assert_eq!(prompt.text, "Be brief.\n\nHi");
+
+        let chatml = PromptTemplate {
+            text: "<|im_start|>system\n{{system}}<|im_end|>\n<|im_start|>user\n{{user}}<|im_end|>"
+                .into(),
+        };
+        prompt.apply_template(&chatml);
+        assert_eq!(
+            prompt.text,
+            "<|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nHi<|im_end|>"
+        );
+    }
+}
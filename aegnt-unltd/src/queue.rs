@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueError {
+    /// `max_queue_depth` requests are already waiting.
+    Full,
+    /// No slot freed up within `max_queue_wait`.
+    TimedOut,
+}
+
+impl std::fmt::Display for QueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueError::Full => write!(f, "request queue is full"),
+            QueueError::TimedOut => write!(f, "timed out waiting for a model slot"),
+        }
+    }
+}
+
+/// Where a request waits in line: a freed slot goes to the oldest waiter of
+/// the highest priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "high" => Ok(Priority::High),
+            "normal" => Ok(Priority::Normal),
+            "low" => Ok(Priority::Low),
+            other => Err(format!(
+                "invalid priority '{}'; expected high, normal or low",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Slots {
+    free: usize,
+    /// Waiters by priority, lowest first, each in arrival order.
+    waiters: [VecDeque<oneshot::Sender<()>>; 3],
+}
+
+impl Slots {
+    fn waiting(&self) -> usize {
+        self.waiters.iter().map(VecDeque::len).sum()
+    }
+}
+
+/// A fixed number of model slots with a bounded wait queue in front.
+///
+/// Requests that find every slot busy wait in line up to `max_depth` deep
+/// for at most `max_wait`; anything beyond that is rejected.
+pub struct BoundedQueue {
+    slots: Arc<Mutex<Slots>>,
+    capacity: usize,
+    max_depth: usize,
+    max_wait: Duration,
+}
+
+/// A held model slot, handed to the next waiter when dropped.
+pub struct QueuePermit {
+    slots: Arc<Mutex<Slots>>,
+}
+
+impl BoundedQueue {
+    pub fn new(slots: usize, max_depth: usize, max_wait: Duration) -> Self {
+        Self {
+            slots: Arc::new(Mutex::new(Slots {
+                free: slots,
+                ..Default::default()
+            })),
+            capacity: slots,
+            max_depth,
+            max_wait,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Slots> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Requests currently waiting for a slot.
+    pub fn depth(&self) -> usize {
+        self.lock().waiting()
+    }
+
+    /// Requests currently holding a slot.
+    pub fn in_flight(&self) -> usize {
+        self.capacity - self.lock().free
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn max_wait(&self) -> Duration {
+        self.max_wait
+    }
+
+    /// How full the wait queue is, from 0.0 (empty) to 1.0 (full).
+    pub fn load(&self) -> f32 {
+        if self.max_depth == 0 {
+            return if self.in_flight() == self.capacity {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        (self.depth() as f32 / self.max_depth as f32).min(1.0)
+    }
+
+    pub async fn acquire(&self, priority: Priority) -> Result<QueuePermit, QueueError> {
+        let permit = || QueuePermit {
+            slots: self.slots.clone(),
+        };
+        let mut granted = {
+            let mut slots = self.lock();
+            if slots.free > 0 {
+                slots.free -= 1;
+                return Ok(permit());
+            }
+            if slots.waiting() >= self.max_depth {
+                return Err(QueueError::Full);
+            }
+            let (tx, rx) = oneshot::channel();
+            slots.waiters[priority as usize].push_back(tx);
+            rx
+        };
+
+        if let Ok(Ok(())) = tokio::time::timeout(self.max_wait, &mut granted).await {
+            return Ok(permit());
+        }
+        // A slot handed over just as the wait ran out is still ours
+        granted.close();
+        match granted.try_recv() {
+            Ok(()) => Ok(permit()),
+            Err(_) => {
+                self.lock()
+                    .waiters
+                    .iter_mut()
+                    .for_each(|w| w.retain(|tx| !tx.is_closed()));
+                Err(QueueError::TimedOut)
+            }
+        }
+    }
+}
+
+impl Drop for QueuePermit {
+    fn drop(&mut self) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        while let Some(next) = slots.waiters.iter_mut().rev().find_map(VecDeque::pop_front) {
+            // Waiters that gave up have dropped their receiver
+            if next.send(()).is_ok() {
+                return;
+            }
+        }
+        slots.free += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_overflow_and_timeout() {
+        let queue = Arc::new(BoundedQueue::new(1, 1, Duration::from_millis(20)));
+        let held = queue.acquire(Priority::Normal).await.unwrap();
+
+        // One waiter fits in the queue and times out; a second is rejected.
+        let waiter = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire(Priority::Normal).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(queue.depth(), 1);
+        assert_eq!(queue.load(), 1.0);
+        assert_eq!(
+            queue.acquire(Priority::High).await.err(),
+            Some(QueueError::Full)
+        );
+        assert_eq!(waiter.await.unwrap(), Err(QueueError::TimedOut));
+        assert_eq!(queue.depth(), 0);
+
+        drop(held);
+        assert_eq!(queue.in_flight(), 0);
+        assert!(queue.acquire(Priority::Normal).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_jumps_the_line() {
+        let queue = Arc::new(BoundedQueue::new(1, 3, Duration::from_secs(5)));
+        let held = queue.acquire(Priority::Normal).await.unwrap();
+
+        let (order_tx, mut order) = tokio::sync::mpsc::unbounded_channel();
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let (queue, order_tx) = (queue.clone(), order_tx.clone());
+            tokio::spawn(async move {
+                let _permit = queue.acquire(priority).await.unwrap();
+                order_tx.send(priority).unwrap();
+            });
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        drop(held);
+        let mut served = Vec::new();
+        for _ in 0..3 {
+            served.push(order.recv().await.unwrap());
+        }
+        assert_eq!(
+            served,
+            vec![Priority::High, Priority::Normal, Priority::Low]
+        );
+    }
+}
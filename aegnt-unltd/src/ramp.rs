@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Concurrency limiter that ramps linearly from `initial` to `max` in-flight
+/// requests over `period`, so a freshly started (not yet warm) model isn't
+/// hit by a burst of traffic the moment the server comes up.
+pub struct SlowStart {
+    started: Instant,
+    period: Duration,
+    initial: usize,
+    max: usize,
+    in_flight: AtomicUsize,
+    released: Notify,
+}
+
+pub struct SlowStartPermit {
+    ramp: Arc<SlowStart>,
+}
+
+impl SlowStart {
+    pub fn new(period: Duration, initial: usize, max: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            period,
+            initial: initial.max(1),
+            max: max.max(initial.max(1)),
+            in_flight: AtomicUsize::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Current concurrency limit given the time since startup.
+    pub fn limit(&self) -> usize {
+        let elapsed = self.started.elapsed();
+        if elapsed >= self.period || self.period.is_zero() {
+            return self.max;
+        }
+        let progress = elapsed.as_secs_f64() / self.period.as_secs_f64();
+        self.initial + ((self.max - self.initial) as f64 * progress) as usize
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Waits until a slot is available under the current ramp limit.
+    pub async fn acquire(self: &Arc<Self>) -> SlowStartPermit {
+        loop {
+            let current = self.in_flight.load(Ordering::Acquire);
+            if current < self.limit() {
+                if self
+                    .in_flight
+                    .compare_exchange(current, current + 1, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    return SlowStartPermit { ramp: self.clone() };
+                }
+                continue;
+            }
+            // The limit also grows with time, so don't wait on releases alone.
+            let _ = tokio::time::timeout(Duration::from_millis(50), self.released.notified()).await;
+        }
+    }
+}
+
+impl Drop for SlowStartPermit {
+    fn drop(&mut self) {
+        self.ramp.in_flight.fetch_sub(1, Ordering::AcqRel);
+        self.ramp.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limit_ramps_to_max() {
+        let ramp = Arc::new(SlowStart::new(Duration::from_millis(0), 1, 8));
+        assert_eq!(ramp.limit(), 8);
+
+        let ramp = Arc::new(SlowStart::new(Duration::from_secs(60), 2, 8));
+        assert_eq!(ramp.limit(), 2);
+
+        let first = ramp.acquire().await;
+        let _second = ramp.acquire().await;
+        assert_eq!(ramp.in_flight(), 2);
+        drop(first);
+        assert_eq!(ramp.in_flight(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// Backing store for fixed-window rate-limit counters.
+///
+/// The in-memory store only limits a single instance; use a shared store
+/// (e.g. [`RedisStore`]) to enforce one budget across a fleet.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Records a hit for `key` in the current window of length `window` and
+    /// returns the number of hits in that window, including this one.
+    async fn hit(&self, key: &str, window: Duration) -> Result<u64, String>;
+}
+
+#[derive(Default)]
+pub struct InMemoryStore {
+    windows: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryStore {
+    async fn hit(&self, key: &str, window: Duration) -> Result<u64, String> {
+        let current = window_index(window);
+        let mut windows = self.windows.lock().await;
+
+        // Drop counters from past windows so idle clients don't accumulate.
+        windows.retain(|_, (index, _)| *index == current);
+
+        let entry = windows.entry(key.to_string()).or_insert((current, 0));
+        entry.1 += 1;
+        Ok(entry.1)
+    }
+}
+
+#[cfg(feature = "redis")]
+pub struct RedisStore {
+    client: redis::Client,
+    prefix: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisStore {
+    pub fn new(url: &str, prefix: impl Into<String>) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| e.to_string())?;
+        Ok(Self {
+            client,
+            prefix: prefix.into(),
+        })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl RateLimitStore for RedisStore {
+    async fn hit(&self, key: &str, window: Duration) -> Result<u64, String> {
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        let redis_key = format!("{}:{}:{}", self.prefix, key, window_index(window));
+
+        let (count,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&redis_key, 1)
+            .expire(&redis_key, window.as_secs().max(1) as i64)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(count)
+    }
+}
+
+/// Index of the fixed window containing "now", shared by all instances.
+fn window_index(window: Duration) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    now / (window.as_millis() as u64).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_counts_per_key() {
+        let store = InMemoryStore::default();
+        let window = Duration::from_secs(3600);
+
+        assert_eq!(store.hit("a", window).await.unwrap(), 1);
+        assert_eq!(store.hit("a", window).await.unwrap(), 2);
+        assert_eq!(store.hit("b", window).await.unwrap(), 1);
+    }
+}
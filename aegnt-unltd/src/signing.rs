@@ -0,0 +1,60 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Hex-encoded SHA-256 of the raw directive, as carried in `Response.input_hash`.
+pub fn input_hash(input: &str) -> String {
+    hex::encode(Sha256::digest(input.as_bytes()))
+}
+
+/// HMAC-SHA256 over `(request_id, input_hash, content)`, hex-encoded.
+pub fn sign(key: &str, request_id: &str, input_hash: &str, content: &str) -> String {
+    hex::encode(
+        mac(key, request_id, input_hash, content)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+/// Checks a signature produced by [`sign`] in constant time.
+pub fn verify(
+    key: &str,
+    request_id: &str,
+    input_hash: &str,
+    content: &str,
+    signature: &str,
+) -> bool {
+    let Ok(expected) = hex::decode(signature) else {
+        return false;
+    };
+    mac(key, request_id, input_hash, content)
+        .verify_slice(&expected)
+        .is_ok()
+}
+
+fn mac(key: &str, request_id: &str, input_hash: &str, content: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    for (i, part) in [request_id, input_hash, content].iter().enumerate() {
+        if i > 0 {
+            mac.update(b"\n");
+        }
+        mac.update(part.as_bytes());
+    }
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let hash = input_hash("plan Q3");
+        let signature = sign("secret", "req-1", &hash, "content");
+
+        assert!(verify("secret", "req-1", &hash, "content", &signature));
+        assert!(!verify("secret", "req-1", &hash, "tampered", &signature));
+        assert!(!verify("other", "req-1", &hash, "content", &signature));
+    }
+}
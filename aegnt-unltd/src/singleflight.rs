@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OnceCell};
+
+/// Coalesces concurrent calls that share a key into a single computation.
+///
+/// Unlike a cache, entries only live while the computation is in flight;
+/// a call arriving after it finished starts a fresh one.
+pub struct SingleFlight<T> {
+    inflight: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    /// Runs `compute` unless an identical call is already in flight, in which
+    /// case its result is awaited instead. Returns the value and whether it
+    /// was shared from another call.
+    pub async fn run<F, Fut>(&self, key: String, compute: F) -> (T, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let cell = self
+            .inflight
+            .lock()
+            .await
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        // If the computing call is cancelled, a waiter runs its own `compute`.
+        let mut computed_here = false;
+        let value = cell
+            .get_or_init(|| {
+                computed_here = true;
+                compute()
+            })
+            .await
+            .clone();
+
+        let mut inflight = self.inflight.lock().await;
+        if inflight.get(&key).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+            inflight.remove(&key);
+        }
+
+        (value, !computed_here)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_one_computation() {
+        let flight = Arc::new(SingleFlight::<usize>::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let flight = flight.clone();
+                let calls = calls.clone();
+                tokio::spawn(async move {
+                    flight
+                        .run("same".to_string(), || async {
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            calls.fetch_add(1, Ordering::SeqCst) + 1
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        let mut shared = 0;
+        for task in tasks {
+            let (value, was_shared) = task.await.unwrap();
+            assert_eq!(value, 1);
+            shared += was_shared as usize;
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(shared, 4);
+    }
+}
@@ -0,0 +1,227 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::PathBuf;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+/// Where frames a slow reader hasn't taken yet overflow to.
+#[derive(Debug, Clone)]
+pub struct SpillConfig {
+    /// Frames held in memory before spilling starts.
+    pub memory_frames: usize,
+    /// Spill file; removed once drained.
+    pub path: PathBuf,
+    /// Largest the spill file may grow. Beyond it the writer waits for the
+    /// reader, as it would without spilling.
+    pub max_bytes: u64,
+}
+
+/// Relays `frames` to the returned receiver. While the reader keeps up,
+/// frames pass through a `memory_frames` channel; once that's full they're
+/// appended to the spill file and drained, in order, as the reader catches
+/// up.
+pub fn relay<T>(mut frames: mpsc::Receiver<T>, config: SpillConfig) -> mpsc::Receiver<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(config.memory_frames.max(1));
+    tokio::spawn(async move {
+        let mut spill = Spill::new(config);
+        let mut open = true;
+        while open || spill.pending > 0 {
+            tokio::select! {
+                frame = frames.recv(), if open => match frame {
+                    Some(frame) => {
+                        // Nothing overtakes spilled frames
+                        let frame = if spill.pending == 0 {
+                            match tx.try_send(frame) {
+                                Ok(()) => continue,
+                                Err(mpsc::error::TrySendError::Full(frame)) => frame,
+                                Err(mpsc::error::TrySendError::Closed(_)) => break,
+                            }
+                        } else {
+                            frame
+                        };
+                        if let Err(frame) = spill.push(frame).await {
+                            if !send_in_order(&tx, &mut spill, frame).await {
+                                break;
+                            }
+                        }
+                    }
+                    None => open = false,
+                },
+                permit = tx.reserve(), if spill.pending > 0 => match (permit, spill.pop().await) {
+                    (Ok(permit), Some(frame)) => permit.send(frame),
+                    _ => break,
+                },
+            }
+        }
+        spill.remove().await;
+    });
+    rx
+}
+
+/// Sends `frame` once everything spilled before it has been, waiting on
+/// the reader; false when it has gone.
+async fn send_in_order<T: DeserializeOwned>(
+    tx: &mpsc::Sender<T>,
+    spill: &mut Spill,
+    frame: T,
+) -> bool {
+    while spill.pending > 0 {
+        let Some(spilled) = spill.pop().await else {
+            return false;
+        };
+        if tx.send(spilled).await.is_err() {
+            return false;
+        }
+    }
+    tx.send(frame).await.is_ok()
+}
+
+struct Spill {
+    config: SpillConfig,
+    writer: Option<File>,
+    reader: Option<BufReader<File>>,
+    pending: usize,
+    bytes: u64,
+}
+
+impl Spill {
+    fn new(config: SpillConfig) -> Self {
+        Self {
+            config,
+            writer: None,
+            reader: None,
+            pending: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Appends `frame`, or hands it back when the spill is full or can't be
+    /// written.
+    async fn push<T: Serialize>(&mut self, frame: T) -> Result<(), T> {
+        let Ok(mut line) = serde_json::to_string(&frame) else {
+            return Err(frame);
+        };
+        line.push('\n');
+        if self.bytes + line.len() as u64 > self.config.max_bytes {
+            return Err(frame);
+        }
+        if self.writer.is_none() {
+            match self.open().await {
+                Ok(()) => {}
+                Err(e) => {
+                    eprintln!("[spill] can't open {:?}: {}", self.config.path, e);
+                    return Err(frame);
+                }
+            }
+        }
+        let Some(writer) = &mut self.writer else {
+            return Err(frame);
+        };
+        if writer.write_all(line.as_bytes()).await.is_err() || writer.flush().await.is_err() {
+            return Err(frame);
+        }
+        self.bytes += line.len() as u64;
+        self.pending += 1;
+        Ok(())
+    }
+
+    async fn open(&mut self) -> std::io::Result<()> {
+        if let Some(dir) = self.config.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        self.writer = Some(File::create(&self.config.path).await?);
+        self.reader = Some(BufReader::new(File::open(&self.config.path).await?));
+        Ok(())
+    }
+
+    /// The oldest spilled frame.
+    async fn pop<T: DeserializeOwned>(&mut self) -> Option<T> {
+        let mut line = String::new();
+        self.reader.as_mut()?.read_line(&mut line).await.ok()?;
+        self.pending -= 1;
+        if self.pending == 0 {
+            // Drained: start the next spill from an empty file
+            self.remove().await;
+        }
+        serde_json::from_str(&line).ok()
+    }
+
+    async fn remove(&mut self) {
+        if self.writer.take().is_some() {
+            self.reader = None;
+            self.bytes = 0;
+            let _ = tokio::fs::remove_file(&self.config.path).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_spills_for_a_slow_reader_in_order() {
+        let path = std::env::temp_dir().join(format!("aegnt-spill-{}.jsonl", uuid::Uuid::new_v4()));
+        let (tx, frames) = mpsc::channel(1);
+        let mut rx = relay(
+            frames,
+            SpillConfig {
+                memory_frames: 2,
+                path: path.clone(),
+                max_bytes: 1024,
+            },
+        );
+
+        // Nothing reads until every frame is sent, so most of them spill
+        for i in 0..10 {
+            tx.send(format!("frame {}", i)).await.unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(path.exists());
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Some(frame) = rx.recv().await {
+            received.push(frame);
+        }
+        assert_eq!(
+            received,
+            (0..10).map(|i| format!("frame {}", i)).collect::<Vec<_>>()
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_full_spill_waits_for_the_reader() {
+        let path = std::env::temp_dir().join(format!("aegnt-spill-{}.jsonl", uuid::Uuid::new_v4()));
+        let (tx, frames) = mpsc::channel(1);
+        let mut rx = relay(
+            frames,
+            SpillConfig {
+                memory_frames: 1,
+                path,
+                // Room for two frames
+                max_bytes: 24,
+            },
+        );
+        tokio::spawn(async move {
+            for i in 0..10 {
+                tx.send(format!("frame {}", i)).await.unwrap();
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let mut received = Vec::new();
+        while let Some(frame) = rx.recv().await {
+            received.push(frame);
+        }
+        assert_eq!(
+            received,
+            (0..10).map(|i| format!("frame {}", i)).collect::<Vec<_>>()
+        );
+    }
+}
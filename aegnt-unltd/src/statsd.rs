@@ -0,0 +1,63 @@
+use std::net::UdpSocket;
+
+/// Push-based metrics over the StatsD line protocol.
+///
+/// Sends are non-blocking and best-effort: a dropped datagram or an
+/// unreachable collector never slows a request down.
+pub struct StatsdClient {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+impl StatsdClient {
+    /// Connects to the collector at `addr` (`host:port`), prefixing every
+    /// metric name with `prefix.`.
+    pub fn new(addr: &str, prefix: impl Into<String>) -> Result<Self, String> {
+        let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+        socket
+            .connect(addr)
+            .map_err(|e| format!("cannot reach statsd at {}: {}", addr, e))?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Self {
+            socket,
+            prefix: prefix.into(),
+        })
+    }
+
+    pub fn count(&self, name: &str, value: u64) {
+        self.send(name, value, "c");
+    }
+
+    pub fn timing(&self, name: &str, ms: u64) {
+        self.send(name, ms, "ms");
+    }
+
+    fn send(&self, name: &str, value: u64, kind: &str) {
+        let line = format!("{}.{}:{}|{}", self.prefix, name, value, kind);
+        let _ = self.socket.send(line.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sends_statsd_lines() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+        let addr = collector.local_addr().unwrap().to_string();
+
+        let client = StatsdClient::new(&addr, "aegnt").unwrap();
+        client.count("requests.strategy", 1);
+        client.timing("latency.strategy", 42);
+
+        let mut buf = [0u8; 64];
+        let n = collector.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"aegnt.requests.strategy:1|c");
+        let n = collector.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"aegnt.latency.strategy:42|ms");
+    }
+}
@@ -0,0 +1,360 @@
+use crate::prompt::estimate_tokens;
+use crate::Response;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// One frame of a streamed response, serialized as `{ "type", "data" }`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// Progress message shown while the answer is still being prepared.
+    Status(String),
+    ReasoningStepStart {
+        label: String,
+    },
+    Reasoning(String),
+    ReasoningStepEnd {
+        label: String,
+    },
+    Content(String),
+    /// A content chunk stamped with when it was sent, for `timing` streams.
+    #[serde(rename = "content")]
+    TimedContent {
+        text: String,
+        elapsed_ms: u64,
+    },
+    /// Sent just after `done` on `timing` streams.
+    Timing(StreamTiming),
+    Done(Box<Response>),
+    Error(String),
+    /// Generation was stopped through `/process/cancel`.
+    Cancelled,
+}
+
+impl StreamEvent {
+    /// SSE `event:` name for this frame.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StreamEvent::Status(_) => "status",
+            StreamEvent::ReasoningStepStart { .. } => "reasoning_step_start",
+            StreamEvent::Reasoning(_) => "reasoning",
+            StreamEvent::ReasoningStepEnd { .. } => "reasoning_step_end",
+            StreamEvent::Content(_) | StreamEvent::TimedContent { .. } => "content",
+            StreamEvent::Timing(_) => "timing",
+            StreamEvent::Done(_) => "done",
+            StreamEvent::Error(_) => "error",
+            StreamEvent::Cancelled => "cancelled",
+        }
+    }
+}
+
+pub type EventSender = mpsc::Sender<StreamEvent>;
+
+/// Time to first token and throughput of one streamed answer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StreamTiming {
+    pub time_to_first_token_ms: Option<u64>,
+    pub total_ms: u64,
+    pub chunks: usize,
+    pub tokens: usize,
+    /// Tokens per second after the first chunk arrived.
+    pub tokens_per_sec: Option<f32>,
+}
+
+/// Stamps content frames as they are sent and sums them up at the end.
+pub struct TimingRecorder {
+    started: Instant,
+    first_ms: Option<u64>,
+    last_ms: u64,
+    chunks: usize,
+    tokens: usize,
+    first_tokens: usize,
+}
+
+impl TimingRecorder {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            first_ms: None,
+            last_ms: 0,
+            chunks: 0,
+            tokens: 0,
+            first_tokens: 0,
+        }
+    }
+
+    /// Stamps content frames; other frames pass through unchanged.
+    pub fn stamp(&mut self, event: StreamEvent) -> StreamEvent {
+        let StreamEvent::Content(text) = event else {
+            return event;
+        };
+        let elapsed_ms = self.started.elapsed().as_millis() as u64;
+        let tokens = estimate_tokens(&text);
+        if self.first_ms.is_none() {
+            self.first_ms = Some(elapsed_ms);
+            self.first_tokens = tokens;
+        }
+        self.last_ms = elapsed_ms;
+        self.chunks += 1;
+        self.tokens += tokens;
+        StreamEvent::TimedContent { text, elapsed_ms }
+    }
+
+    pub fn summary(&self) -> StreamTiming {
+        let generating_ms = self.first_ms.map(|first| self.last_ms - first);
+        StreamTiming {
+            time_to_first_token_ms: self.first_ms,
+            total_ms: self.started.elapsed().as_millis() as u64,
+            chunks: self.chunks,
+            tokens: self.tokens,
+            tokens_per_sec: generating_ms
+                .filter(|ms| *ms > 0)
+                .map(|ms| (self.tokens - self.first_tokens) as f32 * 1000.0 / ms as f32),
+        }
+    }
+}
+
+/// How streamed text is cut into frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamGranularity {
+    /// One frame per word-sized token.
+    #[default]
+    Token,
+    /// Tokens are buffered and flushed as whole sentences, trading a little
+    /// latency for chunks that render cleanly on their own.
+    Sentence,
+}
+
+/// Where generation stops for one path, so quick answers end early while
+/// strategies run long.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StopConditions {
+    /// Generation stops before the first of these; the sequence itself is
+    /// not emitted.
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// Most tokens emitted.
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+}
+
+impl StopConditions {
+    /// Cuts `text` where generation would have stopped, describing why.
+    pub fn apply(&self, text: &mut String) -> Option<String> {
+        let mut reason = None;
+        if let Some((at, seq)) = self
+            .stop
+            .iter()
+            .filter(|seq| !seq.is_empty())
+            .filter_map(|seq| text.find(seq.as_str()).map(|at| (at, seq)))
+            .min_by_key(|(at, _)| *at)
+        {
+            text.truncate(at);
+            reason = Some(format!("stop sequence {:?}", seq));
+        }
+
+        if let Some(max) = self.max_tokens {
+            let end: usize = tokens(text).take(max).map(str::len).sum();
+            if end < text.len() {
+                text.truncate(end);
+                reason = Some(format!("max_tokens {}", max));
+            }
+        }
+        reason
+    }
+}
+
+/// Splits text into word-sized tokens, keeping trailing whitespace so the
+/// tokens concatenate back to the original.
+pub fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive(char::is_whitespace)
+}
+
+/// Groups tokens into sentences, flushing after a token that ends in
+/// `.`, `!` or `?` (and at the end of the text).
+pub fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    for token in tokens(text) {
+        end += token.len();
+        if token.trim_end().ends_with(['.', '!', '?']) {
+            sentences.push(&text[start..end]);
+            start = end;
+        }
+    }
+    if start < end {
+        sentences.push(&text[start..end]);
+    }
+    sentences
+}
+
+fn chunks(text: &str, granularity: StreamGranularity) -> Vec<&str> {
+    match granularity {
+        StreamGranularity::Token => tokens(text).collect(),
+        StreamGranularity::Sentence => sentences(text),
+    }
+}
+
+/// Phase each kind of reasoning trace line belongs to, by the wording the
+/// line opens with (after any `1.` numbering).
+const STEP_PHASES: &[(&str, &str)] = &[
+    ("Loaded", "retrieval"),
+    ("Knowledge:", "retrieval"),
+    ("Grounding", "grounding"),
+    ("Generated", "drafting"),
+    ("Considered structured context", "context"),
+    ("Tool", "tools"),
+    ("Self-check", "self-check"),
+    ("Repetition check", "repetition check"),
+    ("Map-reduce", "map-reduce"),
+    ("Prompt trimmed", "prompt"),
+    ("Generation stopped", "stop condition"),
+    ("Citations", "citations"),
+    ("Restyled", "restyling"),
+    ("Translated", "translation"),
+    ("Content truncated", "truncation"),
+    ("Degraded", "fallback"),
+    ("Routed", "routing"),
+    ("Tokens used", "budget"),
+];
+
+/// Phase label for trace line `step`, else its position (`step 3`).
+fn step_label(step: &str, position: usize) -> String {
+    let text = step.trim_start_matches(|c: char| c.is_ascii_digit());
+    let text = text.strip_prefix(". ").unwrap_or(step);
+    STEP_PHASES
+        .iter()
+        .find(|(opening, _)| text.starts_with(opening))
+        .map_or_else(
+            || format!("step {}", position),
+            |(_, phase)| phase.to_string(),
+        )
+}
+
+/// Emits the routing decision as a `classification` step, then the
+/// reasoning trace, one step per line wrapped in boundaries labelled with
+/// its phase, followed by the answer content.
+pub async fn emit_response(
+    events: &EventSender,
+    response: &Response,
+    granularity: StreamGranularity,
+) -> Result<(), ()> {
+    let send = |event| async { events.send(event).await.map_err(|_| ()) };
+
+    let classification = Some(response.routing.reason.as_str()).filter(|reason| !reason.is_empty());
+    let trace = response
+        .reasoning_trace
+        .iter()
+        .flat_map(|trace| trace.lines());
+    let steps = classification
+        .map(|reason| ("classification".to_string(), reason))
+        .into_iter()
+        .chain(
+            trace
+                .enumerate()
+                .map(|(i, step)| (step_label(step, i + 1), step)),
+        );
+    for (label, step) in steps {
+        send(StreamEvent::ReasoningStepStart {
+            label: label.clone(),
+        })
+        .await?;
+        for chunk in chunks(step, granularity) {
+            send(StreamEvent::Reasoning(chunk.to_string())).await?;
+        }
+        send(StreamEvent::ReasoningStepEnd { label }).await?;
+    }
+
+    for chunk in chunks(&response.content, granularity) {
+        send(StreamEvent::Content(chunk.to_string())).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_are_labelled_by_phase() {
+        let trace = [
+            "1. Loaded 2 knowledge chunk(s) from \"knowledge\" [a.md#0]",
+            "2. Grounding against constitution",
+            "3. Generated plan",
+            "Tokens used: reasoning 8/8, answer 10/10",
+            "Something new",
+        ];
+        let labels: Vec<String> = trace
+            .iter()
+            .enumerate()
+            .map(|(i, step)| step_label(step, i + 1))
+            .collect();
+        assert_eq!(
+            labels,
+            vec!["retrieval", "grounding", "drafting", "budget", "step 5"]
+        );
+    }
+
+    #[test]
+    fn test_sentence_chunks_reassemble() {
+        let text = "Ship it. Really? Yes! Then e.g. trailing words";
+        let chunks = sentences(text);
+        assert_eq!(
+            chunks,
+            vec![
+                "Ship it. ",
+                "Really? ",
+                "Yes! ",
+                "Then e.g. ",
+                "trailing words"
+            ]
+        );
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn test_timing_stamps_content() {
+        let mut timing = TimingRecorder::start();
+        let status = timing.stamp(StreamEvent::Status("thinking".to_string()));
+        assert!(matches!(status, StreamEvent::Status(_)));
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let first = timing.stamp(StreamEvent::Content("Ship ".to_string()));
+        let StreamEvent::TimedContent { elapsed_ms, .. } = first else {
+            panic!("expected a timed content frame, got {:?}", first);
+        };
+        assert!(elapsed_ms >= 5);
+        timing.stamp(StreamEvent::Content("it".to_string()));
+
+        let summary = timing.summary();
+        assert_eq!(summary.time_to_first_token_ms, Some(elapsed_ms));
+        assert_eq!((summary.chunks, summary.tokens), (2, 3));
+    }
+
+    #[test]
+    fn test_stop_conditions() {
+        let quick = StopConditions {
+            stop: vec!["\n\n".to_string()],
+            max_tokens: Some(3),
+        };
+
+        let mut text = "Yes.\n\nHere is more detail".to_string();
+        assert_eq!(
+            quick.apply(&mut text).as_deref(),
+            Some("stop sequence \"\\n\\n\"")
+        );
+        assert_eq!(text, "Yes.");
+
+        let mut text = "one two three four five".to_string();
+        assert_eq!(quick.apply(&mut text).as_deref(), Some("max_tokens 3"));
+        assert_eq!(text, "one two three ");
+
+        let mut text = "one two three".to_string();
+        assert_eq!(quick.apply(&mut text), None);
+        assert_eq!(text, "one two three");
+    }
+}
@@ -0,0 +1,98 @@
+use serde_json::Value;
+
+/// Resolves a JSONPath-style selector against a structured input document.
+///
+/// Supports the subset needed to point at a directive field: the root `$`,
+/// dotted member access (`$.task.text`), bracketed member access
+/// (`$['task']`) and array indices (`$.items[0]`).
+pub fn select<'a>(doc: &'a Value, path: &str) -> Result<&'a Value, String> {
+    let rest = path
+        .strip_prefix('$')
+        .ok_or_else(|| format!("path must start with '$': {}", path))?;
+
+    let mut current = doc;
+    let mut chars = rest.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut key = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next == '.' || next == '[' {
+                        break;
+                    }
+                    key.push(next);
+                    chars.next();
+                }
+                if key.is_empty() {
+                    return Err(format!("empty member name in path: {}", path));
+                }
+                current = current
+                    .get(&key)
+                    .ok_or_else(|| format!("no field '{}' in input", key))?;
+            }
+            '[' => {
+                let mut segment = String::new();
+                for next in chars.by_ref() {
+                    if next == ']' {
+                        break;
+                    }
+                    segment.push(next);
+                }
+                let quoted = segment
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| segment.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+
+                current = match quoted {
+                    Some(key) => current
+                        .get(key)
+                        .ok_or_else(|| format!("no field '{}' in input", key))?,
+                    None => {
+                        let index: usize = segment
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("invalid index '{}' in path", segment))?;
+                        current
+                            .get(index)
+                            .ok_or_else(|| format!("index {} out of range", index))?
+                    }
+                };
+            }
+            other => return Err(format!("unexpected '{}' in path: {}", other, path)),
+        }
+    }
+
+    Ok(current)
+}
+
+/// Extracts the directive text at `path`, returning the full document
+/// alongside it so the remaining fields can be used as context.
+pub fn extract_directive(raw: &str, path: &str) -> Result<(String, Value), String> {
+    let doc: Value =
+        serde_json::from_str(raw).map_err(|e| format!("input is not valid JSON: {}", e))?;
+
+    let directive = match select(&doc, path)? {
+        Value::String(s) => s.clone(),
+        other => return Err(format!("directive at {} is not a string: {}", path, other)),
+    };
+
+    Ok((directive, doc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_paths() {
+        let doc = json!({ "task": { "text": "plan Q3" }, "items": ["a", "b"], "odd key": 1 });
+
+        assert_eq!(select(&doc, "$.task.text").unwrap(), "plan Q3");
+        assert_eq!(select(&doc, "$.items[1]").unwrap(), "b");
+        assert_eq!(select(&doc, "$['odd key']").unwrap(), 1);
+        assert!(select(&doc, "$.missing").is_err());
+        assert!(select(&doc, "task").is_err());
+    }
+}
@@ -0,0 +1,19 @@
+use crate::webhook::ClassificationEvent;
+use async_trait::async_trait;
+
+/// Destination for classification decisions, e.g. a data warehouse feed
+/// for offline routing analysis. Called once per classified request, off
+/// the request path.
+#[async_trait]
+pub trait ClassificationSink: Send + Sync {
+    async fn record(&self, event: ClassificationEvent);
+}
+
+/// Discards every event; the default sink.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSink;
+
+#[async_trait]
+impl ClassificationSink for NoopSink {
+    async fn record(&self, _event: ClassificationEvent) {}
+}
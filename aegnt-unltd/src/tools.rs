@@ -0,0 +1,98 @@
+use async_trait::async_trait;
+
+/// Line prefix the model uses to request a tool: `CALL <tool>: <input>`.
+pub const CALL_PREFIX: &str = "CALL ";
+
+/// A tool the fast model can call while answering a QuickAction.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    /// Name the model calls the tool by.
+    fn name(&self) -> &str;
+
+    /// One line telling the model what the tool does.
+    fn description(&self) -> &str;
+
+    async fn call(&self, input: &str) -> Result<String, String>;
+}
+
+/// Suffix the model may end a call with to say how sure it is the tool is
+/// needed: `CALL <tool>: <input> (confidence 0.8)`.
+const CONFIDENCE_PREFIX: &str = " (confidence ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub name: String,
+    pub input: String,
+    /// Confidence (0-1) the model stated for the call, if any.
+    pub confidence: Option<f32>,
+}
+
+/// The tool call in a model step, if the step requests one rather than
+/// answering.
+pub fn parse_call(step: &str) -> Option<ToolCall> {
+    step.lines().find_map(|line| {
+        let (name, input) = line.trim().strip_prefix(CALL_PREFIX)?.split_once(':')?;
+        let name = name.trim();
+        let (input, confidence) = stated_confidence(input.trim());
+        (!name.is_empty()).then(|| ToolCall {
+            name: name.to_string(),
+            input: input.to_string(),
+            confidence,
+        })
+    })
+}
+
+/// Splits a trailing `(confidence 0.8)` off a call's input.
+fn stated_confidence(input: &str) -> (&str, Option<f32>) {
+    let stated = input.strip_suffix(')').and_then(|rest| {
+        let (input, value) = rest.rsplit_once(CONFIDENCE_PREFIX)?;
+        Some((input.trim_end(), value.trim().parse().ok()?))
+    });
+    match stated {
+        Some((input, confidence)) => (input, Some(confidence)),
+        None => (input, None),
+    }
+}
+
+/// Lists `tools` and the call syntax, for the system prompt.
+pub fn instruction(tools: &[std::sync::Arc<dyn Tool>]) -> String {
+    let listed: Vec<String> = tools
+        .iter()
+        .map(|tool| format!("- {}: {}", tool.name(), tool.description()))
+        .collect();
+    format!(
+        "You can call these tools, one per step, by replying with a single line \
+         `{}<tool>: <input>{}0-1)` stating how sure you are the tool is needed:\n{}\n\
+         Answer normally once you have what you need.",
+        CALL_PREFIX,
+        CONFIDENCE_PREFIX,
+        listed.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_calls() {
+        assert_eq!(
+            parse_call("Let me check.\nCALL weather: Berlin"),
+            Some(ToolCall {
+                name: "weather".to_string(),
+                input: "Berlin".to_string(),
+                confidence: None,
+            })
+        );
+        assert_eq!(
+            parse_call("CALL weather: Berlin (confidence 0.4)"),
+            Some(ToolCall {
+                name: "weather".to_string(),
+                input: "Berlin".to_string(),
+                confidence: Some(0.4),
+            })
+        );
+        assert_eq!(parse_call("It is sunny in Berlin."), None);
+        assert_eq!(parse_call("CALL : Berlin"), None);
+    }
+}
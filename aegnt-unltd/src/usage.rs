@@ -0,0 +1,23 @@
+use crate::prompt::estimate_tokens;
+use serde::{Deserialize, Serialize};
+
+/// Token usage for one model call, normalized across providers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Counted locally rather than reported by the provider.
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl Usage {
+    /// Local estimate for backends that report nothing.
+    pub fn estimate(prompt_tokens: usize, completion: &str) -> Self {
+        Self {
+            prompt_tokens: prompt_tokens as u64,
+            completion_tokens: estimate_tokens(completion) as u64,
+            estimated: true,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+/// Where a directive's text came from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputSource {
+    #[default]
+    Text,
+    /// Speech-to-text, typically without punctuation or capitalization.
+    Voice,
+}
+
+/// What is done with a directive marked `source: voice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoicePreprocessing {
+    /// Classify the restored transcript, but answer the transcript as given.
+    Classify,
+    /// Replace the transcript with the restored text throughout.
+    Restore,
+}
+
+/// Hesitations speech-to-text transcribes as words.
+const FILLERS: &[&str] = &["um", "umm", "uh", "uhh", "uhm", "er", "erm", "hmm"];
+
+/// Words that open a spoken question.
+const QUESTION_WORDS: &[&str] = &[
+    "what", "how", "why", "when", "where", "who", "which", "whose", "can", "could", "would",
+    "should", "will", "is", "are", "do", "does", "did", "shall",
+];
+
+/// Restores the punctuation and capitalization a transcript lacks: drops
+/// fillers, capitalizes the first word and "I", and ends the sentence with
+/// `?` when it opens like a question, `.` otherwise.
+pub fn restore(transcript: &str) -> String {
+    let words: Vec<String> = transcript
+        .split_whitespace()
+        .filter(|word| {
+            let bare = word
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase();
+            !FILLERS.contains(&bare.as_str())
+        })
+        .map(|word| {
+            let lower = word.to_lowercase();
+            if lower == "i" || lower.starts_with("i'") {
+                capitalize(word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect();
+    let Some(first) = words.first() else {
+        return String::new();
+    };
+
+    let first_lower = first.to_lowercase();
+    let mut restored = capitalize(first);
+    for word in &words[1..] {
+        restored.push(' ');
+        restored.push_str(word);
+    }
+    if !restored.ends_with(['.', '?', '!']) {
+        let question = QUESTION_WORDS.contains(&first_lower.trim_end_matches(','));
+        restored.push(if question { '?' } else { '.' });
+    }
+    restored
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restores_punctuation_and_capitals() {
+        assert_eq!(
+            restore("um can i move the standup to friday"),
+            "Can I move the standup to friday?"
+        );
+        assert_eq!(restore("uh fix the login bug"), "Fix the login bug.");
+        assert_eq!(restore("Ship it!"), "Ship it!");
+        assert_eq!(restore(" um "), "");
+    }
+}
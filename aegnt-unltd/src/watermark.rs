@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// Zero-width characters carrying the invisible watermark's bits.
+const ZERO: char = '\u{200B}';
+const ONE: char = '\u{200C}';
+/// Brackets the encoded bits so they can be found again.
+const FRAME: char = '\u{2060}';
+
+/// Visible footer line; the request id follows it.
+const FOOTER_PREFIX: &str = "— Generated by aegnt-unltd, request ";
+
+/// How generated content is watermarked with its request id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatermarkStyle {
+    /// Zero-width characters appended to the content.
+    Invisible,
+    /// A footer line naming the request.
+    Footer,
+}
+
+/// `content` with `request_id` embedded in `style`.
+pub fn apply(content: &str, request_id: &str, style: WatermarkStyle) -> String {
+    match style {
+        WatermarkStyle::Invisible => {
+            let bits = request_id
+                .bytes()
+                .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1))
+                .map(|bit| if bit == 1 { ONE } else { ZERO });
+            let mut marked = content.to_string();
+            marked.push(FRAME);
+            marked.extend(bits);
+            marked.push(FRAME);
+            marked
+        }
+        WatermarkStyle::Footer => format!("{}\n\n{}{}", content, FOOTER_PREFIX, request_id),
+    }
+}
+
+/// The request id watermarked into `text`, in either style, if it carries
+/// one — i.e. whether the text came from this brain, and from which request.
+pub fn verify(text: &str) -> Option<String> {
+    invisible(text).or_else(|| {
+        text.lines()
+            .rev()
+            .find_map(|line| line.trim().strip_prefix(FOOTER_PREFIX))
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+    })
+}
+
+fn invisible(text: &str) -> Option<String> {
+    let mut framed = text.split(FRAME).skip(1);
+    let bits: Vec<u8> = framed
+        .next()?
+        .chars()
+        .map(|c| match c {
+            ZERO => Some(0),
+            ONE => Some(1),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    if bits.is_empty() || !bits.len().is_multiple_of(8) {
+        return None;
+    }
+    let bytes = bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, bit| acc << 1 | bit))
+        .collect();
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_both_styles() {
+        let id = "3f2a9c1e-req";
+        let invisible = apply("Ship it.", id, WatermarkStyle::Invisible);
+        assert!(invisible.starts_with("Ship it."));
+        assert_eq!(invisible.chars().filter(|c| c.is_alphanumeric()).count(), 6);
+        assert_eq!(verify(&invisible).as_deref(), Some(id));
+
+        let footer = apply("Ship it.", id, WatermarkStyle::Footer);
+        assert_eq!(verify(&footer).as_deref(), Some(id));
+
+        assert_eq!(verify("Ship it."), None);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{signing, Intent, RoutingDecision};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Longest input excerpt sent to a webhook, in characters.
+const SUMMARY_CHARS: usize = 200;
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One classification decision, POSTed to classification webhooks and
+/// handed to the brain's `ClassificationSink`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassificationEvent {
+    pub request_id: String,
+    pub intent: Intent,
+    pub input_summary: String,
+    /// SHA-256 of the input, for joining with responses without storing it.
+    pub input_hash: String,
+    pub confidence: f32,
+    /// Routing steps that decided the intent, in order (e.g. `keyword
+    /// match 'roadmap'`).
+    pub features: Vec<String>,
+}
+
+impl ClassificationEvent {
+    pub fn new(request_id: &str, routing: &RoutingDecision, input: &str) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            intent: routing.classified_intent.clone(),
+            input_summary: summarize(input),
+            input_hash: signing::input_hash(input),
+            confidence: routing.confidence,
+            features: routing
+                .reason
+                .split("; ")
+                .filter(|step| !step.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// Fire-and-forget delivery of classification events.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    http: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// POSTs `event` to `url` in the background. Delivery failures are
+    /// logged and never surface to the caller.
+    pub fn fire(&self, url: &str, event: ClassificationEvent) {
+        let http = self.http.clone();
+        let url = url.to_string();
+        tokio::spawn(async move {
+            let result = http
+                .post(&url)
+                .json(&event)
+                .send()
+                .await
+                .and_then(|res| res.error_for_status());
+            if let Err(e) = result {
+                eprintln!(
+                    "[webhook] delivery of {} to {} failed: {}",
+                    event.request_id, url, e
+                );
+            }
+        });
+    }
+}
+
+fn summarize(input: &str) -> String {
+    let input = input.trim();
+    match input.char_indices().nth(SUMMARY_CHARS) {
+        Some((end, _)) => format!("{}…", &input[..end]),
+        None => input.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_is_truncated_on_char_boundary() {
+        assert_eq!(summarize("  short  "), "short");
+
+        let long = "é".repeat(SUMMARY_CHARS + 5);
+        let summary = summarize(&long);
+        assert!(summary.ends_with('…'));
+        assert_eq!(summary.chars().count(), SUMMARY_CHARS + 1);
+    }
+}